@@ -0,0 +1,587 @@
+//ENEMY AI
+//each enemy runs a small state machine every tick, switching states on whether it can see the
+//player and how far away they are
+//
+//  idle    stands still until it sees the player, or starts walking its patrol route
+//  patrol  walks from waypoint to waypoint, looping back to the first
+//  chase   runs at the player, or to where it last saw them
+//  attack  stands and faces the player while they are close and in sight, hitting them when
+//          right next to them and shooting otherwise, with a cooldown between attacks
+//  flee    runs off when badly hurt, until the player is out of sight
+//  dead    does nothing, the death animation plays out
+//
+//idle and patrolling enemies only notice a player in front of them, once chasing they track the
+//player all around as long as nothing is in the way
+//patrolling and chasing find their way round walls with the pathfinder, fleeing just backs off
+//
+//getting hurt makes an enemy flinch for a moment whatever its state, and alerts it to the
+//attacker if it had not noticed them yet
+//
+//the AI only decides to attack, the world carries the attack out (see World::enemy_attack in
+//sim.rs) since it needs the player, the other projectiles and the random numbers
+
+use crate::animation::AnimKind;
+use crate::damage::{Damage, Hurt};
+use crate::map::Map;
+use crate::pathfinding::NavGrid;
+use crate::raycast::has_line_of_sight;
+use crate::world::{deg_to_rad, Enemy, Player};
+
+//distances are in tiles so they work for any tile size
+const SIGHT_RANGE: f64 = 8.0; //furthest away the player can be noticed
+const ATTACK_RANGE: f64 = 4.0; //close enough to stop and attack
+const MELEE_RANGE: f64 = 1.5; //close enough to hit instead of shoot, takes in diagonal neighbours
+const FALLOFF_RANGE: f64 = 6.0; //shots get less accurate out to here
+const MIN_ACCURACY: f64 = 0.2; //how accurate shots are from further away
+const WAYPOINT_REACH: f64 = 0.1; //how close counts as arriving at a waypoint
+const VIEW_CONE: f64 = 120.0; //degrees in front of an idle or patrolling enemy it can see
+const FLEE_HEALTH: f64 = 0.25; //with less than this share of its hit points left it runs
+const PAIN_TIME: f64 = 0.25; //seconds a hit stops the enemy in its tracks
+
+//seconds between attacks
+const MELEE_COOLDOWN: f64 = 0.8;
+const RANGED_COOLDOWN: f64 = 1.5;
+
+//speeds in tiles per second
+const WALK_SPEED: f64 = 1.5;
+const RUN_SPEED: f64 = 2.5;
+
+//what an enemy is doing right now
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AiState {
+    Idle,
+    Patrol,
+    Chase,
+    Attack,
+    Flee,
+    Dead,
+}
+
+impl AiState {
+    //animation to play while in this state
+    pub fn animation(self) -> AnimKind {
+        match self {
+            AiState::Idle => AnimKind::Idle,
+            AiState::Patrol | AiState::Chase | AiState::Flee => AnimKind::Walk,
+            AiState::Attack => AnimKind::Attack,
+            AiState::Dead => AnimKind::Death,
+        }
+    }
+}
+
+//how an enemy shoots when the player is not close enough to hit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ranged {
+    Projectile, //fires something that flies and can be dodged
+    Hitscan,    //hits or misses the moment it fires
+}
+
+//an attack an enemy makes this tick
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Attack {
+    Melee,
+    Ranged(Ranged),
+}
+
+//share of shots that would hit the player this many tiles away, 1.0 up close down to MIN_ACCURACY
+pub fn accuracy(distance: f64) -> f64 {
+    (1.0 - distance / FALLOFF_RANGE).clamp(MIN_ACCURACY, 1.0)
+}
+
+//BRAIN STRUCTURE
+//the state machine plus what it remembers between ticks
+pub struct Brain {
+    pub state: AiState,
+    pub waypoints: Vec<(f64, f64)>, //patrol route in world space, empty to stand guard
+    pub next_waypoint: usize,       //index of the waypoint being walked to
+    pub last_seen: (f64, f64),      //where the player was when last in sight
+    pub pain_left: f64,             //seconds left flinching from a hit
+    pub cooldown: f64,              //seconds until it can attack again
+    pub ranged: Ranged,             //how it attacks from a distance
+}
+
+impl Brain {
+    pub fn new() -> Self {
+        Self {
+            state: AiState::Idle,
+            waypoints: Vec::new(),
+            next_waypoint: 0,
+            last_seen: (0.0, 0.0),
+            pain_left: 0.0,
+            cooldown: 0.0,
+            ranged: Ranged::Projectile,
+        }
+    }
+
+    //state to fall back to when there is no player to deal with
+    fn at_rest(&self) -> AiState {
+        if self.waypoints.is_empty() {
+            AiState::Idle
+        } else {
+            AiState::Patrol
+        }
+    }
+}
+
+impl Default for Brain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//what the enemy knows about the player this tick
+struct Senses {
+    distance: f64,  //to the player in tiles
+    in_sight: bool, //nothing solid in between and close enough to see
+    in_view: bool,  //in sight and inside the view cone
+}
+
+//difference between two angles in degrees, 0 to 180
+fn angle_between(a: f64, b: f64) -> f64 {
+    let d = (a - b).rem_euclid(360.0);
+    d.min(360.0 - d)
+}
+
+impl Enemy {
+    //one AI tick: pick a state from what the enemy sees, then act on it
+    //returns the attack it makes, if any
+    pub fn update(&mut self, player: &Player, map: &Map, nav: &NavGrid, dt: f64) -> Option<Attack> {
+        self.brain.cooldown = (self.brain.cooldown - dt).max(0.0);
+        //flinching, nothing else happens until it is over
+        if self.alive && self.brain.pain_left > 0.0 {
+            self.brain.pain_left -= dt;
+            return None;
+        }
+
+        let ts = map.tile_size;
+        let senses = self.sense(player, map);
+        if senses.in_sight {
+            self.brain.last_seen = (player.px, player.py);
+        }
+
+        self.brain.state = self.next_state(&senses);
+        let mut attack = None;
+
+        match self.brain.state {
+            AiState::Idle | AiState::Dead => {}
+            AiState::Patrol => {
+                //a waypoint that can't be reached is skipped
+                let target = self.brain.waypoints[self.brain.next_waypoint];
+                if self.travel(target, WALK_SPEED * ts * dt, map, nav) != Some(false) {
+                    self.brain.next_waypoint =
+                        (self.brain.next_waypoint + 1) % self.brain.waypoints.len();
+                }
+            }
+            AiState::Chase => {
+                //reaching the last place the player was seen without spotting them means giving up,
+                //so does finding there is no way there
+                match self.travel(self.brain.last_seen, RUN_SPEED * ts * dt, map, nav) {
+                    Some(true) if !senses.in_sight => self.brain.state = self.brain.at_rest(),
+                    None => self.brain.state = self.brain.at_rest(),
+                    _ => {}
+                }
+            }
+            AiState::Attack => {
+                self.facing = self.angle_to(player.px, player.py);
+                if self.brain.cooldown <= 0.0 {
+                    let (chosen, cooldown) = if senses.distance <= MELEE_RANGE {
+                        (Attack::Melee, MELEE_COOLDOWN)
+                    } else {
+                        (Attack::Ranged(self.brain.ranged), RANGED_COOLDOWN)
+                    };
+                    attack = Some(chosen);
+                    self.brain.cooldown = cooldown;
+                    self.animation.time = 0.0; //the attack animation plays once per attack
+                }
+            }
+            AiState::Flee => {
+                //heads for the point a tile straight away from the player, it has to be further
+                //off than WAYPOINT_REACH or walk_to would count it as already there
+                let away = deg_to_rad(self.angle_to(player.px, player.py) + 180.0);
+                let target = (self.x + away.cos() * ts, self.y - away.sin() * ts);
+                self.walk_to(target, RUN_SPEED * ts * dt, map);
+            }
+        }
+
+        self.animation.play(self.brain.state.animation());
+        attack
+    }
+
+    //takes a hit from something at from, see Health::take
+    pub fn hurt(&mut self, damage: Damage, from: (f64, f64)) -> Hurt {
+        let hurt = self.health.take(damage);
+        match hurt {
+            Hurt::Ignored => {}
+            Hurt::Pain(_) => {
+                self.brain.pain_left = PAIN_TIME;
+                self.animation.play(AnimKind::Pain);
+                self.animation.time = 0.0; //flinch again even if still flinching
+                if matches!(self.brain.state, AiState::Idle | AiState::Patrol) {
+                    self.brain.state = AiState::Chase;
+                    self.brain.last_seen = from;
+                }
+            }
+            Hurt::Killed => {
+                self.alive = false;
+                self.brain.state = AiState::Dead;
+                self.animation.play(AnimKind::Death);
+            }
+        }
+        hurt
+    }
+
+    fn sense(&self, player: &Player, map: &Map) -> Senses {
+        let dx = player.px - self.x;
+        let dy = player.py - self.y;
+        let distance = (dx * dx + dy * dy).sqrt() / map.tile_size;
+        let in_sight = distance <= SIGHT_RANGE
+            && has_line_of_sight(map, (self.x, self.y), (player.px, player.py));
+        let ahead = angle_between(self.angle_to(player.px, player.py), self.facing);
+        Senses {
+            distance,
+            in_sight,
+            in_view: in_sight && ahead <= VIEW_CONE / 2.0,
+        }
+    }
+
+    //the state transitions, the state stays the same unless something here changes it
+    fn next_state(&self, senses: &Senses) -> AiState {
+        let state = self.brain.state;
+        if !self.alive {
+            return AiState::Dead;
+        }
+        let wounded = self.health.fraction() < FLEE_HEALTH;
+        match state {
+            AiState::Dead => AiState::Dead,
+            AiState::Idle | AiState::Patrol => {
+                if senses.in_view {
+                    AiState::Chase
+                } else {
+                    self.brain.at_rest()
+                }
+            }
+            AiState::Chase | AiState::Attack => {
+                if senses.in_sight && wounded {
+                    AiState::Flee
+                } else if senses.in_sight && senses.distance <= ATTACK_RANGE {
+                    AiState::Attack
+                } else {
+                    AiState::Chase
+                }
+            }
+            AiState::Flee => {
+                if !senses.in_sight {
+                    AiState::Chase
+                } else {
+                    AiState::Flee
+                }
+            }
+        }
+    }
+
+    //moves up to step along the path to target, true once it is there, None if there is no path
+    fn travel(&mut self, target: (f64, f64), step: f64, map: &Map, nav: &NavGrid) -> Option<bool> {
+        let next = nav.next_waypoint(map, (self.x, self.y), target)?;
+        let arrived = self.walk_to(next, step, map);
+        Some(arrived && next == target)
+    }
+
+    //moves up to step straight towards target, sliding along walls, true once it is there
+    fn walk_to(&mut self, target: (f64, f64), step: f64, map: &Map) -> bool {
+        let dx = target.0 - self.x;
+        let dy = target.1 - self.y;
+        let dist = (dx * dx + dy * dy).sqrt();
+        if dist <= WAYPOINT_REACH * map.tile_size {
+            return true;
+        }
+        self.facing = self.angle_to(target.0, target.1);
+
+        let t = (step / dist).min(1.0);
+        //x and y are tried apart so a blocked direction does not stop movement along the other
+        if !map.is_wall_at(self.x + dx * t, self.y) {
+            self.x += dx * t;
+        }
+        if !map.is_wall_at(self.x, self.y + dy * t) {
+            self.y += dy * t;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::ENEMY_HP;
+
+    //builds a map from rows of '#' walls and '.' floor, tiles 64 wide
+    fn build_map(rows: &[&str]) -> Map {
+        let tiles = rows
+            .iter()
+            .flat_map(|row| row.chars().map(|c| if c == '#' { 1 } else { 0 }))
+            .collect();
+        Map::new(rows[0].len(), rows.len(), 64.0, tiles)
+    }
+
+    //one long open room
+    fn hall() -> Map {
+        build_map(&[
+            "############",
+            "#..........#",
+            "#..........#",
+            "#..........#",
+            "############",
+        ])
+    }
+
+    //the hall cut in two by a wall down column 5
+    fn split_hall() -> Map {
+        build_map(&[
+            "############",
+            "#....#.....#",
+            "#....#.....#",
+            "#....#.....#",
+            "############",
+        ])
+    }
+
+    //one AI tick, a tenth of a second long
+    fn tick(enemy: &mut Enemy, player: &Player, map: &Map) -> Option<Attack> {
+        enemy.update(player, map, &NavGrid::from_map(map), 0.1)
+    }
+
+    #[test]
+    fn idle_notices_only_inside_the_view_cone() {
+        let map = hall();
+        let player = Player::new(9, 2, 0.0, &map);
+
+        //looking the other way
+        let mut enemy = Enemy::new(2, 2, 180.0, &map);
+        tick(&mut enemy, &player, &map);
+        assert_eq!(enemy.brain.state, AiState::Idle);
+
+        //the player is 7 tiles east and 1 tile north, a few degrees off straight ahead
+        let mut enemy = Enemy::new(2, 3, 0.0, &map);
+        tick(&mut enemy, &player, &map);
+        assert_eq!(enemy.brain.state, AiState::Chase);
+
+        //facing north the player is 82 degrees to the right, outside the 60 either side
+        let mut enemy = Enemy::new(2, 3, 90.0, &map);
+        tick(&mut enemy, &player, &map);
+        assert_eq!(enemy.brain.state, AiState::Idle);
+    }
+
+    #[test]
+    fn idle_does_not_see_through_walls_or_too_far() {
+        let map = split_hall();
+        let player = Player::new(8, 2, 0.0, &map);
+        let mut enemy = Enemy::new(2, 2, 0.0, &map);
+        tick(&mut enemy, &player, &map);
+        assert_eq!(enemy.brain.state, AiState::Idle);
+
+        let map = build_map(&["#############", "#...........#", "#############"]);
+        let player = Player::new(11, 1, 0.0, &map);
+        let mut enemy = Enemy::new(1, 1, 0.0, &map);
+        assert!(enemy.sense(&player, &map).distance > SIGHT_RANGE);
+        tick(&mut enemy, &player, &map);
+        assert_eq!(enemy.brain.state, AiState::Idle);
+    }
+
+    #[test]
+    fn chase_turns_to_attack_within_range() {
+        let map = hall();
+        let player = Player::new(9, 2, 0.0, &map);
+        let mut enemy = Enemy::new(3, 2, 0.0, &map);
+        enemy.brain.state = AiState::Chase;
+
+        //6 tiles off, keep running
+        assert_eq!(tick(&mut enemy, &player, &map), None);
+        assert_eq!(enemy.brain.state, AiState::Chase);
+        assert!(enemy.x > 3.5 * 64.0, "should have moved towards the player");
+
+        //4 tiles off, stop and shoot
+        let mut enemy = Enemy::new(5, 2, 180.0, &map);
+        enemy.brain.state = AiState::Chase;
+        let attack = tick(&mut enemy, &player, &map);
+        assert_eq!(enemy.brain.state, AiState::Attack);
+        assert_eq!(attack, Some(Attack::Ranged(Ranged::Projectile)));
+        assert_eq!(enemy.facing, 0.0, "turns to face the player");
+    }
+
+    #[test]
+    fn chase_gives_up_where_the_player_was_last_seen() {
+        let map = split_hall();
+        let player = Player::new(8, 2, 0.0, &map);
+        let mut enemy = Enemy::new(3, 2, 0.0, &map);
+        enemy.brain.state = AiState::Chase;
+        enemy.brain.last_seen = (enemy.x, enemy.y); //already there and no one in sight
+        tick(&mut enemy, &player, &map);
+        assert_eq!(enemy.brain.state, AiState::Idle);
+    }
+
+    #[test]
+    fn wounded_enemy_flees_until_out_of_sight() {
+        let map = hall();
+        let player = Player::new(8, 2, 0.0, &map);
+        let mut enemy = Enemy::new(5, 2, 0.0, &map);
+        enemy.brain.state = AiState::Attack;
+        enemy.health.hp = ENEMY_HP * FLEE_HEALTH - 1.0;
+
+        let x = enemy.x;
+        assert_eq!(tick(&mut enemy, &player, &map), None);
+        assert_eq!(enemy.brain.state, AiState::Flee);
+        assert!(enemy.x < x, "should run away from the player");
+
+        //once a wall is between them it goes back to chasing, round by the gap under the wall
+        let map = build_map(&[
+            "############",
+            "#....#.....#",
+            "#....#.....#",
+            "#..........#",
+            "############",
+        ]);
+        let player = Player::new(8, 1, 0.0, &map);
+        let mut enemy = Enemy::new(3, 1, 0.0, &map);
+        enemy.brain.state = AiState::Flee;
+        enemy.brain.last_seen = (player.px, player.py);
+        enemy.health.hp = 1.0;
+        tick(&mut enemy, &player, &map);
+        assert_eq!(enemy.brain.state, AiState::Chase);
+    }
+
+    #[test]
+    fn fleeing_keeps_moving_at_real_frame_rates() {
+        //a step at 120 frames a second is shorter than WAYPOINT_REACH, it still has to move
+        let map = hall();
+        let nav = NavGrid::from_map(&map);
+        let player = Player::new(8, 2, 0.0, &map);
+        let mut enemy = Enemy::new(5, 2, 0.0, &map);
+        enemy.brain.state = AiState::Flee;
+        enemy.health.hp = 1.0;
+
+        let x = enemy.x;
+        for _ in 0..120 {
+            enemy.update(&player, &map, &nav, 1.0 / 120.0);
+        }
+        assert_eq!(enemy.brain.state, AiState::Flee);
+        assert!(
+            x - enemy.x > 2.0 * 64.0,
+            "should run about 2.5 tiles in a second, ran {}",
+            (x - enemy.x) / 64.0
+        );
+    }
+
+    #[test]
+    fn healthy_enemy_does_not_flee() {
+        let map = hall();
+        let player = Player::new(4, 2, 0.0, &map);
+        let mut enemy = Enemy::new(3, 2, 0.0, &map);
+        enemy.brain.state = AiState::Chase;
+        tick(&mut enemy, &player, &map);
+        assert_eq!(enemy.brain.state, AiState::Attack);
+    }
+
+    #[test]
+    fn dead_stays_dead() {
+        let map = hall();
+        let player = Player::new(4, 2, 0.0, &map);
+        let mut enemy = Enemy::new(3, 2, 0.0, &map);
+        enemy.alive = false;
+        enemy.brain.state = AiState::Dead;
+        for _ in 0..10 {
+            assert_eq!(tick(&mut enemy, &player, &map), None);
+            assert_eq!(enemy.brain.state, AiState::Dead);
+        }
+        assert_eq!(enemy.animation.kind, AnimKind::Death);
+        assert_eq!(map.to_tile(enemy.x, enemy.y), (3, 2));
+    }
+
+    #[test]
+    fn patrol_waypoints_advance_and_wrap_around() {
+        //the player is shut in a room of their own so the patrol is never interrupted
+        let map = build_map(&["##########", "#......#.#", "#......###", "##########"]);
+        let player = Player::new(8, 1, 0.0, &map);
+        let nav = NavGrid::from_map(&map);
+        let mut enemy = Enemy::new(1, 1, 0.0, &map);
+        enemy.brain.waypoints = vec![map.tile_center(1, 1), map.tile_center(4, 2)];
+        enemy.brain.state = AiState::Patrol;
+
+        //standing on the first waypoint already, so it heads for the second
+        enemy.update(&player, &map, &nav, 0.1);
+        assert_eq!(enemy.brain.next_waypoint, 1);
+
+        //about 3 tiles at walking speed, a few seconds there and back
+        let mut visited = vec![1];
+        for _ in 0..100 {
+            enemy.update(&player, &map, &nav, 0.1);
+            assert_eq!(enemy.brain.state, AiState::Patrol);
+            if visited.last() != Some(&enemy.brain.next_waypoint) {
+                visited.push(enemy.brain.next_waypoint);
+            }
+        }
+        assert_eq!(&visited[..3], &[1, 0, 1]);
+    }
+
+    #[test]
+    fn melee_only_within_melee_range() {
+        let map = hall();
+        let player = Player::new(5, 2, 0.0, &map);
+
+        //next tile over, exactly one tile apart
+        let mut enemy = Enemy::new(4, 2, 0.0, &map);
+        enemy.brain.state = AiState::Attack;
+        assert_eq!(tick(&mut enemy, &player, &map), Some(Attack::Melee));
+
+        //the diagonal neighbour is about 1.41 tiles away, still in reach
+        let mut enemy = Enemy::new(4, 1, 0.0, &map);
+        enemy.brain.state = AiState::Attack;
+        assert_eq!(tick(&mut enemy, &player, &map), Some(Attack::Melee));
+
+        //two tiles apart shoots instead
+        let mut enemy = Enemy::new(3, 2, 0.0, &map);
+        enemy.brain.state = AiState::Attack;
+        assert_eq!(
+            tick(&mut enemy, &player, &map),
+            Some(Attack::Ranged(Ranged::Projectile))
+        );
+    }
+
+    #[test]
+    fn no_attack_while_cooling_down() {
+        let map = hall();
+        let nav = NavGrid::from_map(&map);
+        let player = Player::new(5, 2, 0.0, &map);
+        let mut enemy = Enemy::new(4, 2, 0.0, &map);
+        enemy.brain.state = AiState::Attack;
+        assert_eq!(enemy.update(&player, &map, &nav, 0.25), Some(Attack::Melee));
+        assert_eq!(enemy.brain.cooldown, MELEE_COOLDOWN);
+
+        //0.8 seconds of cooldown is 3 quarter second ticks without an attack, then it hits again
+        for _ in 0..3 {
+            assert_eq!(enemy.update(&player, &map, &nav, 0.25), None);
+            assert!(enemy.brain.cooldown > 0.0);
+        }
+        assert_eq!(enemy.update(&player, &map, &nav, 0.25), Some(Attack::Melee));
+    }
+
+    #[test]
+    fn hitscan_enemies_shoot_hitscan() {
+        let map = hall();
+        let player = Player::new(6, 2, 0.0, &map);
+        let mut enemy = Enemy::new(3, 2, 0.0, &map);
+        enemy.brain.state = AiState::Attack;
+        enemy.brain.ranged = Ranged::Hitscan;
+        assert_eq!(
+            tick(&mut enemy, &player, &map),
+            Some(Attack::Ranged(Ranged::Hitscan))
+        );
+    }
+
+    #[test]
+    fn accuracy_falls_off_to_the_minimum() {
+        assert_eq!(accuracy(0.0), 1.0);
+        assert_eq!(accuracy(3.0), 0.5);
+        assert_eq!(accuracy(FALLOFF_RANGE * (1.0 - MIN_ACCURACY)), MIN_ACCURACY);
+        assert_eq!(accuracy(FALLOFF_RANGE), MIN_ACCURACY);
+        assert_eq!(accuracy(100.0), MIN_ACCURACY);
+    }
+}
@@ -0,0 +1,437 @@
+//ANIMATION
+//sprite sheets split into square cells, with named animations playing runs of those cells
+//each sheet comes with a small text file saying where its image is and what the animations are
+//
+//  // comment lines start with two slashes
+//  image enemy_sheet.png       sheet image, relative to this file
+//  cell 64                     cells are 64x64, counted row by row from the top left
+//  anim idle 2 loop 0 1        idle plays cells 0 and 1 at 2 frames a second, over and over
+//  anim death 8 once 9 10 11   death plays once and stays on its last frame
+//  rotations 8 12              optional, every frame is drawn from 8 sides (see below)
+//
+//animations are idle, walk, attack, pain and death, only idle is required
+//
+//with rotations the cells in anim lines are the frames seen from the front, and the same frame
+//seen from the next side round is that many cells (12 above) further on, so the sheet is a
+//block of front frames, then a block of frames seen from 45 degrees, and so on
+//sides go counterclockwise round the sprite seen from above, the way angles go in the game
+
+use crate::parse::{expect_args, parse_arg, parse_err, LineError};
+use std::fmt;
+
+//which animation a sprite is playing, picked from what it is doing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnimKind {
+    Idle,
+    Walk,
+    Attack,
+    Pain,
+    Death,
+}
+
+impl AnimKind {
+    pub const ALL: [AnimKind; 5] = [
+        AnimKind::Idle,
+        AnimKind::Walk,
+        AnimKind::Attack,
+        AnimKind::Pain,
+        AnimKind::Death,
+    ];
+
+    //name used in sheet files
+    pub fn name(self) -> &'static str {
+        match self {
+            AnimKind::Idle => "idle",
+            AnimKind::Walk => "walk",
+            AnimKind::Attack => "attack",
+            AnimKind::Pain => "pain",
+            AnimKind::Death => "death",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.name() == name)
+    }
+}
+
+//ONE ANIMATION
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animation {
+    pub cells: Vec<usize>, //sheet cells in the order they are shown
+    pub fps: f64,          //frames shown per second
+    pub looping: bool,     //starts over at the end, otherwise stays on the last frame
+}
+
+impl Animation {
+    //which of the cells to show after playing for time seconds
+    pub fn frame(&self, time: f64) -> usize {
+        let frame = (time.max(0.0) * self.fps) as usize;
+        if self.looping {
+            frame % self.cells.len()
+        } else {
+            frame.min(self.cells.len() - 1)
+        }
+    }
+
+    //seconds until a one shot animation reaches its last frame, None for looping ones
+    pub fn duration(&self) -> Option<f64> {
+        if self.looping {
+            None
+        } else {
+            Some(self.cells.len() as f64 / self.fps)
+        }
+    }
+}
+
+//ERRORS
+#[derive(Debug)]
+pub enum SheetError {
+    //metadata file is malformed
+    Parse { line: usize, msg: String },
+    //image is too small for the cells the animations use
+    TooSmall { cells: usize, needed: usize },
+}
+
+impl fmt::Display for SheetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SheetError::Parse { line, msg } => write!(f, "line {}: {}", line, msg),
+            SheetError::TooSmall { cells, needed } => write!(
+                f,
+                "sheet image only has {} cells, the animations use {}",
+                cells, needed
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SheetError {}
+
+impl From<LineError> for SheetError {
+    fn from((line, msg): LineError) -> Self {
+        SheetError::Parse { line, msg }
+    }
+}
+
+//SPRITE SHEET
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteSheet {
+    pub image: String,          //image file, relative to the metadata file
+    pub cell_size: usize,       //width and height of one frame
+    pub rotations: usize,       //sides each frame is drawn from, 1 if it looks the same from all
+    pub rotation_stride: usize, //cells from one side's frame to the next side's
+    pub animations: Vec<(AnimKind, Animation)>,
+}
+
+impl SpriteSheet {
+    pub fn parse(text: &str) -> Result<Self, SheetError> {
+        let mut image = None;
+        let mut cell_size = None;
+        let mut animations: Vec<(AnimKind, Animation)> = Vec::new();
+        let mut rotations = (1, 0);
+
+        for (i, raw) in text.lines().enumerate() {
+            let line_no = i + 1; //editors count lines from 1
+            let line = raw.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            let key = words.next().unwrap_or("");
+            let args: Vec<&str> = words.collect();
+
+            match key {
+                "image" => {
+                    expect_args(line_no, &args, 1, "image <file>")?;
+                    image = Some(args[0].to_string());
+                }
+                "cell" => {
+                    expect_args(line_no, &args, 1, "cell <size>")?;
+                    let size: usize = parse_arg(line_no, "cell size", args[0])?;
+                    if size == 0 {
+                        return Err(parse_err(line_no, "cell size must be positive".to_string()));
+                    }
+                    cell_size = Some(size);
+                }
+                "anim" => {
+                    if args.len() < 4 {
+                        return Err(parse_err(
+                            line_no,
+                            "expected `anim <name> <fps> <loop|once> <cells...>`".to_string(),
+                        ));
+                    }
+                    let kind = AnimKind::from_name(args[0])
+                        .ok_or_else(|| (line_no, format!("unknown animation `{}`", args[0])))?;
+                    if animations.iter().any(|&(k, _)| k == kind) {
+                        return Err(parse_err(
+                            line_no,
+                            format!("animation `{}` is defined twice", args[0]),
+                        ));
+                    }
+                    let fps: f64 = parse_arg(line_no, "frame rate", args[1])?;
+                    if !fps.is_finite() || fps <= 0.0 {
+                        return Err(parse_err(
+                            line_no,
+                            format!("frame rate must be positive, got {}", fps),
+                        ));
+                    }
+                    let looping = match args[2] {
+                        "loop" => true,
+                        "once" => false,
+                        other => {
+                            return Err(parse_err(
+                                line_no,
+                                format!("expected `loop` or `once`, got `{}`", other),
+                            ))
+                        }
+                    };
+                    let cells = args[3..]
+                        .iter()
+                        .map(|arg| parse_arg(line_no, "cell", arg))
+                        .collect::<Result<Vec<usize>, _>>()?;
+                    animations.push((
+                        kind,
+                        Animation {
+                            cells,
+                            fps,
+                            looping,
+                        },
+                    ));
+                }
+                "rotations" => {
+                    expect_args(line_no, &args, 2, "rotations <count> <cells apart>")?;
+                    let count: usize = parse_arg(line_no, "rotation count", args[0])?;
+                    let stride: usize = parse_arg(line_no, "rotation cells apart", args[1])?;
+                    if count == 0 {
+                        return Err(parse_err(
+                            line_no,
+                            "rotation count must be positive".to_string(),
+                        ));
+                    }
+                    if count > 1 && stride == 0 {
+                        return Err(parse_err(
+                            line_no,
+                            "rotations must be at least one cell apart".to_string(),
+                        ));
+                    }
+                    rotations = (count, stride);
+                }
+                _ => return Err(parse_err(line_no, format!("unknown keyword `{}`", key))),
+            }
+        }
+
+        let last = text.lines().count();
+        let image = image.ok_or_else(|| (last, "missing `image`".to_string()))?;
+        let cell_size = cell_size.ok_or_else(|| (last, "missing `cell`".to_string()))?;
+        if !animations.iter().any(|&(k, _)| k == AnimKind::Idle) {
+            return Err(parse_err(last, "missing `anim idle`".to_string()));
+        }
+
+        Ok(Self {
+            image,
+            cell_size,
+            rotations: rotations.0,
+            rotation_stride: rotations.1,
+            animations,
+        })
+    }
+
+    pub fn animation(&self, kind: AnimKind) -> Option<&Animation> {
+        self.animations
+            .iter()
+            .find(|&&(k, _)| k == kind)
+            .map(|(_, anim)| anim)
+    }
+
+    //how many cells the image needs for every animation to have its frames from every side
+    pub fn cells_needed(&self) -> usize {
+        let last_side = (self.rotations - 1) * self.rotation_stride;
+        self.animations
+            .iter()
+            .flat_map(|(_, anim)| anim.cells.iter())
+            .max()
+            .map_or(0, |&cell| cell + last_side + 1)
+    }
+
+    //cell to show for a front frame seen from view_angle degrees counterclockwise of the front
+    //the angle is rounded to the nearest side the sheet has
+    pub fn rotated_cell(&self, cell: usize, view_angle: f64) -> usize {
+        let step = 360.0 / self.rotations as f64;
+        let side = (view_angle.rem_euclid(360.0) / step).round() as usize % self.rotations;
+        cell + side * self.rotation_stride
+    }
+}
+
+//ANIMATOR
+//what an animated thing is playing and for how long, the sheet is looked up when drawing
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Animator {
+    pub kind: AnimKind,
+    pub time: f64, //seconds since the animation started
+}
+
+impl Animator {
+    pub fn new() -> Self {
+        Self {
+            kind: AnimKind::Idle,
+            time: 0.0,
+        }
+    }
+
+    //switches animation, starting it from the first frame, playing the same one again keeps going
+    pub fn play(&mut self, kind: AnimKind) {
+        if self.kind != kind {
+            self.kind = kind;
+            self.time = 0.0;
+        }
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        self.time += dt;
+    }
+}
+
+impl Default for Animator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::line_error;
+
+    const HEADER: &str = "image sheet.png\ncell 64\n";
+
+    //line number and message of the error a sheet file fails with
+    fn parse_error(text: &str) -> LineError {
+        line_error(SpriteSheet::parse(text))
+    }
+
+    //sheet with 8 sides, each side's frames 12 cells after the last
+    fn rotated_sheet() -> SpriteSheet {
+        SpriteSheet::parse(&format!("{}anim idle 2 loop 0 1\nrotations 8 12\n", HEADER)).unwrap()
+    }
+
+    #[test]
+    fn reads_animations_and_rotations() {
+        let sheet = rotated_sheet();
+        assert_eq!((sheet.image.as_str(), sheet.cell_size), ("sheet.png", 64));
+        assert_eq!((sheet.rotations, sheet.rotation_stride), (8, 12));
+        let idle = sheet.animation(AnimKind::Idle).unwrap();
+        assert_eq!((idle.cells.clone(), idle.looping), (vec![0, 1], true));
+        assert_eq!(sheet.animation(AnimKind::Walk), None);
+        //cell 1 seen from the last side is 7 * 12 cells on
+        assert_eq!(sheet.cells_needed(), 86);
+    }
+
+    #[test]
+    fn frames_loop_or_hold_the_last_one() {
+        let anim = Animation {
+            cells: vec![4, 5, 6],
+            fps: 10.0,
+            looping: false,
+        };
+        assert_eq!(anim.frame(0.15), 1);
+        assert_eq!(anim.frame(5.0), 2);
+        assert_eq!(anim.duration(), Some(0.3));
+        let anim = Animation {
+            looping: true,
+            ..anim
+        };
+        assert_eq!(anim.frame(0.35), 0);
+        assert_eq!(anim.duration(), None);
+    }
+
+    #[test]
+    fn rotation_rounds_to_the_nearest_side() {
+        let sheet = rotated_sheet();
+        assert_eq!(sheet.rotated_cell(1, 0.0), 1);
+        assert_eq!(sheet.rotated_cell(1, 22.4), 1);
+        //exactly halfway rounds up to the next side
+        assert_eq!(sheet.rotated_cell(1, 22.5), 13);
+        assert_eq!(sheet.rotated_cell(1, 67.4), 13);
+        assert_eq!(sheet.rotated_cell(1, 67.5), 25);
+        assert_eq!(sheet.rotated_cell(1, 180.0), 49);
+        //the last side rounds back round to the front
+        assert_eq!(sheet.rotated_cell(1, 337.4), 85);
+        assert_eq!(sheet.rotated_cell(1, 337.5), 1);
+        assert_eq!(sheet.rotated_cell(1, 360.0), 1);
+        assert_eq!(sheet.rotated_cell(1, 405.0), 13);
+    }
+
+    #[test]
+    fn negative_angles_count_clockwise() {
+        let sheet = rotated_sheet();
+        assert_eq!(sheet.rotated_cell(0, -10.0), 0);
+        assert_eq!(sheet.rotated_cell(0, -22.5), 0);
+        assert_eq!(sheet.rotated_cell(0, -22.6), 84);
+        assert_eq!(sheet.rotated_cell(0, -45.0), 84);
+        assert_eq!(sheet.rotated_cell(0, -90.0), 72);
+    }
+
+    #[test]
+    fn sheets_without_rotations_look_the_same_from_all_sides() {
+        let sheet = SpriteSheet::parse(&format!("{}anim idle 2 loop 3\n", HEADER)).unwrap();
+        assert_eq!(sheet.rotated_cell(3, 0.0), 3);
+        assert_eq!(sheet.rotated_cell(3, 200.0), 3);
+        assert_eq!(sheet.cells_needed(), 4);
+    }
+
+    #[test]
+    fn bad_rotations() {
+        let text = format!("{}anim idle 2 loop 0\nrotations 8 0\n", HEADER);
+        assert_eq!(
+            parse_error(&text),
+            (4, "rotations must be at least one cell apart".to_string())
+        );
+        let text = format!("{}rotations 0 4\n", HEADER);
+        assert_eq!(
+            parse_error(&text),
+            (3, "rotation count must be positive".to_string())
+        );
+        //one side needs no stride
+        let text = format!("{}anim idle 2 loop 0\nrotations 1 0\n", HEADER);
+        assert!(SpriteSheet::parse(&text).is_ok());
+    }
+
+    #[test]
+    fn missing_idle_image_or_cell() {
+        let text = format!("{}anim walk 6 loop 0 1\n", HEADER);
+        assert_eq!(parse_error(&text), (3, "missing `anim idle`".to_string()));
+        assert_eq!(
+            parse_error("cell 64\nanim idle 2 loop 0\n"),
+            (2, "missing `image`".to_string())
+        );
+        assert_eq!(
+            parse_error("image sheet.png\nanim idle 2 loop 0\n"),
+            (2, "missing `cell`".to_string())
+        );
+    }
+
+    #[test]
+    fn bad_anim_lines() {
+        let text = format!("{}anim idle 2 loop 0\nanim idle 4 loop 1\n", HEADER);
+        assert_eq!(
+            parse_error(&text),
+            (4, "animation `idle` is defined twice".to_string())
+        );
+        let text = format!("{}anim run 2 loop 0\n", HEADER);
+        assert_eq!(
+            parse_error(&text),
+            (3, "unknown animation `run`".to_string())
+        );
+        let text = format!("{}anim idle 2 forever 0\n", HEADER);
+        assert_eq!(
+            parse_error(&text),
+            (3, "expected `loop` or `once`, got `forever`".to_string())
+        );
+        let text = format!("{}anim idle 0 loop 0\n", HEADER);
+        assert_eq!(
+            parse_error(&text),
+            (3, "frame rate must be positive, got 0".to_string())
+        );
+    }
+}
@@ -0,0 +1,354 @@
+//ASSETS
+//finds game files no matter which folder the game is started from
+//
+//with no asset root given, files are looked for next to the executable, in the folders above it
+//(so `cargo run` finds files in the crate root from target/debug) and in the working folder
+//a root given with --assets or the RAYCASTER_ASSETS environment variable is the only place looked
+
+use crate::animation::{SheetError, SpriteSheet};
+use crate::soft::Bitmap;
+use crate::textures::{TextureError, TextureRegistry};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+//environment variable that sets the asset root when --assets is not given
+pub const ASSETS_ENV: &str = "RAYCASTER_ASSETS";
+
+//how many folders above the executable are searched
+const EXE_PARENT_DEPTH: usize = 3;
+
+//ERRORS
+#[derive(Debug)]
+pub enum AssetError {
+    //file is not in any asset root
+    NotFound {
+        name: String,
+        searched: Vec<PathBuf>,
+    },
+    //file was given with an absolute path that does not exist
+    Missing {
+        path: PathBuf,
+    },
+    //file exists but could not be read
+    Io {
+        path: PathBuf,
+        err: io::Error,
+    },
+    //image file is broken or in a format we can't read
+    Image {
+        path: PathBuf,
+        err: image::ImageError,
+    },
+    //texture manifest is malformed
+    Manifest {
+        path: PathBuf,
+        err: TextureError,
+    },
+    //sprite sheet metadata is malformed or does not fit its image
+    Sheet {
+        path: PathBuf,
+        err: SheetError,
+    },
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetError::NotFound { name, searched } => {
+                write!(f, "{} not found, looked in", name)?;
+                for (i, dir) in searched.iter().enumerate() {
+                    let sep = if i == 0 { " " } else { ", " };
+                    write!(f, "{}{}", sep, dir.display())?;
+                }
+                Ok(())
+            }
+            AssetError::Missing { path } => write!(f, "{} does not exist", path.display()),
+            AssetError::Io { path, err } => write!(f, "can't read {}: {}", path.display(), err),
+            AssetError::Image { path, err } => {
+                write!(f, "{} is not a usable image: {}", path.display(), err)
+            }
+            AssetError::Manifest { path, err } => write!(f, "{}: {}", path.display(), err),
+            AssetError::Sheet { path, err } => write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for AssetError {}
+
+//ASSET ROOTS
+pub struct Assets {
+    roots: Vec<PathBuf>, //folders searched in order
+}
+
+impl Assets {
+    //root is the --assets folder, if any, otherwise RAYCASTER_ASSETS or the default search is used
+    pub fn new(root: Option<PathBuf>) -> Self {
+        let root = root.or_else(|| env::var_os(ASSETS_ENV).map(PathBuf::from));
+        match root {
+            Some(root) => Self::with_roots(vec![root]),
+            None => Self::with_roots(default_roots()),
+        }
+    }
+
+    pub fn with_roots(roots: Vec<PathBuf>) -> Self {
+        Self { roots }
+    }
+
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    //full path of a file given relative to the asset root, absolute paths are used as they are
+    pub fn resolve(&self, name: &str) -> Result<PathBuf, AssetError> {
+        let path = Path::new(name);
+        if path.is_absolute() {
+            return if path.exists() {
+                Ok(path.to_path_buf())
+            } else {
+                Err(AssetError::Missing {
+                    path: path.to_path_buf(),
+                })
+            };
+        }
+
+        self.roots
+            .iter()
+            .map(|root| root.join(path))
+            .find(|candidate| candidate.is_file())
+            .ok_or_else(|| AssetError::NotFound {
+                name: name.to_string(),
+                searched: self.roots.clone(),
+            })
+    }
+
+    //reads a text file such as a level
+    pub fn read_to_string(&self, name: &str) -> Result<(PathBuf, String), AssetError> {
+        let path = self.resolve(name)?;
+        match fs::read_to_string(&path) {
+            Ok(text) => Ok((path, text)),
+            Err(err) => Err(AssetError::Io { path, err }),
+        }
+    }
+
+    pub fn load_bitmap(&self, name: &str) -> Result<Bitmap, AssetError> {
+        let path = self.resolve(name)?;
+        load_bitmap_at(&path)
+    }
+
+    //loads the texture manifest and its atlases, never failing so the game can always start
+    //anything missing or broken is drawn with the fallback checkerboard and reported back
+    pub fn load_textures(&self, manifest: &str) -> (TextureRegistry, Vec<AssetError>) {
+        let mut problems = Vec::new();
+
+        let (path, text) = match self.read_to_string(manifest) {
+            Ok(found) => found,
+            Err(e) => {
+                problems.push(e);
+                return (TextureRegistry::new(), problems);
+            }
+        };
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+
+        let parsed = TextureRegistry::parse(&text, |file| {
+            //atlas images sit next to the manifest
+            let image_path = dir.join(file);
+            let loaded = if image_path.is_file() {
+                load_bitmap_at(&image_path)
+            } else {
+                Err(AssetError::NotFound {
+                    name: file.to_string(),
+                    searched: vec![dir.clone()],
+                })
+            };
+            match loaded {
+                Ok(bitmap) => Some(bitmap),
+                Err(e) => {
+                    problems.push(e);
+                    None
+                }
+            }
+        });
+
+        match parsed {
+            Ok(registry) => (registry, problems),
+            Err(err) => {
+                problems.push(AssetError::Manifest { path, err });
+                (TextureRegistry::new(), problems)
+            }
+        }
+    }
+
+    //loads a sprite sheet's metadata and image and animates the named sprite with it
+    //on failure the registry is left alone, so the sprite keeps its still texture
+    pub fn load_sheet(
+        &self,
+        metadata: &str,
+        sprite: &str,
+        textures: &mut TextureRegistry,
+    ) -> Result<(), AssetError> {
+        let (path, text) = self.read_to_string(metadata)?;
+        let sheet = SpriteSheet::parse(&text).map_err(|err| AssetError::Sheet {
+            path: path.clone(),
+            err,
+        })?;
+
+        //the sheet image sits next to its metadata
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let bitmap = load_bitmap_at(&dir.join(&sheet.image))?;
+        textures
+            .add_sheet(sprite, bitmap, sheet)
+            .map_err(|err| AssetError::Sheet { path, err })?;
+        Ok(())
+    }
+}
+
+fn load_bitmap_at(path: &Path) -> Result<Bitmap, AssetError> {
+    Bitmap::load(path).map_err(|err| match err {
+        image::ImageError::IoError(err) => AssetError::Io {
+            path: path.to_path_buf(),
+            err,
+        },
+        err => AssetError::Image {
+            path: path.to_path_buf(),
+            err,
+        },
+    })
+}
+
+//folder of the executable and a few above it, then the working folder
+fn default_roots() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    if let Some(exe_dir) = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    {
+        roots.extend(
+            exe_dir
+                .ancestors()
+                .take(EXE_PARENT_DEPTH + 1)
+                .map(Path::to_path_buf),
+        );
+    }
+    if let Ok(cwd) = env::current_dir() {
+        if !roots.contains(&cwd) {
+            roots.push(cwd);
+        }
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //empty folder of its own for one test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("raycaster_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn default_search_is_the_exe_folder_and_above_then_the_working_folder() {
+        let roots = default_roots();
+        let exe = env::current_exe().unwrap();
+        let above: Vec<&Path> = exe
+            .parent()
+            .unwrap()
+            .ancestors()
+            .take(EXE_PARENT_DEPTH + 1)
+            .collect();
+        assert_eq!(&roots[..above.len()], &above[..]);
+        //the working folder comes last, unless it was one of the folders above the exe already
+        let cwd = env::current_dir().unwrap();
+        assert!(roots.last() == Some(&cwd) || above.contains(&cwd.as_path()));
+        assert_eq!(roots.iter().filter(|&root| *root == cwd).count(), 1);
+    }
+
+    #[test]
+    fn roots_are_searched_in_order() {
+        let dir = temp_dir("roots_in_order");
+        let (first, second) = (dir.join("first"), dir.join("second"));
+        fs::create_dir_all(first.join("maps")).unwrap();
+        fs::create_dir_all(second.join("maps")).unwrap();
+        fs::write(first.join("maps/both.map"), "first").unwrap();
+        fs::write(second.join("maps/both.map"), "second").unwrap();
+        fs::write(second.join("maps/second.map"), "second").unwrap();
+
+        let assets = Assets::with_roots(vec![first.clone(), second.clone()]);
+        assert_eq!(
+            assets.resolve("maps/both.map").unwrap(),
+            first.join("maps/both.map")
+        );
+        assert_eq!(
+            assets.resolve("maps/second.map").unwrap(),
+            second.join("maps/second.map")
+        );
+        let e = assets.resolve("maps/none.map").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            format!(
+                "maps/none.map not found, looked in {}, {}",
+                first.display(),
+                second.display()
+            )
+        );
+
+        //absolute paths skip the roots
+        let path = second.join("maps/second.map");
+        let absolute = path.to_str().unwrap();
+        assert_eq!(assets.resolve(absolute).unwrap(), path);
+        let path = dir.join("none.map");
+        let e = assets.resolve(path.to_str().unwrap()).unwrap_err();
+        assert_eq!(e.to_string(), format!("{} does not exist", path.display()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn assets_flag_beats_the_environment_variable() {
+        //the only test that touches the variable, so tests running alongside can't see it
+        let flag = PathBuf::from("from_flag");
+        let var = PathBuf::from("from_env");
+        env::set_var(ASSETS_ENV, &var);
+        assert_eq!(Assets::new(Some(flag.clone())).roots(), &[flag]);
+        assert_eq!(Assets::new(None).roots(), &[var]);
+        env::remove_var(ASSETS_ENV);
+        assert_eq!(Assets::new(None).roots(), &default_roots()[..]);
+    }
+
+    #[test]
+    fn missing_and_broken_atlases_fall_back_to_the_checkerboard() {
+        let dir = temp_dir("broken_atlases");
+        Bitmap::new(128, 64).save_png(dir.join("good.png")).unwrap();
+        fs::write(dir.join("broken.png"), "not a png").unwrap();
+        fs::write(
+            dir.join("textures.txt"),
+            "atlas good good.png 64\natlas broken broken.png 64\natlas gone gone.png 64\n\
+             texture 1 good 1\ntexture 2 broken 0\ntexture 3 gone 0\n",
+        )
+        .unwrap();
+
+        let assets = Assets::with_roots(vec![dir.clone()]);
+        let (registry, problems) = assets.load_textures("textures.txt");
+        let fallback = TextureRegistry::new().region_or_fallback(0);
+        assert_ne!(registry.region_or_fallback(1), fallback);
+        assert_eq!(registry.region_or_fallback(2), fallback);
+        assert_eq!(registry.region_or_fallback(3), fallback);
+
+        assert_eq!(problems.len(), 2);
+        assert!(
+            matches!(&problems[0], AssetError::Image { path, .. } if path.ends_with("broken.png"))
+        );
+        assert!(matches!(&problems[1], AssetError::NotFound { name, .. } if name == "gone.png"));
+
+        //no manifest at all still gives a registry to draw with
+        let (registry, problems) = assets.load_textures("none.txt");
+        assert_eq!(registry.region_or_fallback(1), fallback);
+        assert!(matches!(&problems[..], [AssetError::NotFound { .. }]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,147 @@
+//DAMAGE
+//hit points, armor and the kinds of damage that wear them down, shared by the player and enemies
+//
+//armor soaks up part of every hit until it runs out, how big a part depends on the damage kind,
+//the rest comes off hit points and at 0 whoever it was is dead
+
+//what caused the damage, armor is better against some kinds than others
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DamageKind {
+    Bullet,
+    Melee,
+    Fire,
+}
+
+impl DamageKind {
+    //share of the damage armor takes instead of hit points
+    fn armor_share(self) -> f64 {
+        match self {
+            DamageKind::Bullet => 0.5,
+            DamageKind::Melee => 0.33,
+            DamageKind::Fire => 0.2, //armor does little against fire
+        }
+    }
+}
+
+//one hit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Damage {
+    pub amount: f64,
+    pub kind: DamageKind,
+}
+
+//what a hit did
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hurt {
+    Ignored,   //already dead or nothing got through
+    Pain(f64), //lost this many hit points and lived
+    Killed,    //this hit took the last hit points
+}
+
+//HEALTH STRUCTURE
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Health {
+    pub hp: f64,
+    pub max_hp: f64,
+    pub armor: f64, //points of damage armor can still soak up
+}
+
+impl Health {
+    pub fn new(max_hp: f64) -> Self {
+        Self {
+            hp: max_hp,
+            max_hp,
+            armor: 0.0,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.hp <= 0.0
+    }
+
+    //hit points left from 0.0 to 1.0
+    pub fn fraction(&self) -> f64 {
+        (self.hp / self.max_hp).clamp(0.0, 1.0)
+    }
+
+    //takes a hit, armor first
+    pub fn take(&mut self, damage: Damage) -> Hurt {
+        if self.is_dead() || damage.amount <= 0.0 {
+            return Hurt::Ignored;
+        }
+        let soaked = (damage.amount * damage.kind.armor_share()).min(self.armor);
+        self.armor -= soaked;
+        let lost = damage.amount - soaked;
+        if lost <= 0.0 {
+            return Hurt::Ignored;
+        }
+        self.hp -= lost;
+        if self.is_dead() {
+            self.hp = 0.0;
+            Hurt::Killed
+        } else {
+            Hurt::Pain(lost)
+        }
+    }
+
+    //adds hit points up to the maximum, false if already full (or dead) so nothing was used
+    pub fn heal(&mut self, amount: f64) -> bool {
+        if self.is_dead() || self.hp >= self.max_hp {
+            return false;
+        }
+        self.hp = (self.hp + amount).min(self.max_hp);
+        true
+    }
+}
+
+//EVENTS
+//things that happened during the last tick, for the game loop, sounds or a HUD to react to
+//enemies are their index in world.enemies
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    EnemyHurt { enemy: usize, damage: Damage },
+    EnemyKilled { enemy: usize, damage: Damage },
+    PlayerHurt { damage: Damage },
+    PlayerKilled { damage: Damage },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(amount: f64, kind: DamageKind) -> Damage {
+        Damage { amount, kind }
+    }
+
+    #[test]
+    fn armor_soaks_its_share_until_used_up() {
+        let mut health = Health::new(100.0);
+        health.armor = 10.0;
+        //half of a bullet goes to armor
+        assert_eq!(health.take(hit(10.0, DamageKind::Bullet)), Hurt::Pain(5.0));
+        assert_eq!((health.hp, health.armor), (95.0, 5.0));
+        //only 5 armor left to soak the 15 it would take
+        assert_eq!(health.take(hit(30.0, DamageKind::Bullet)), Hurt::Pain(25.0));
+        assert_eq!((health.hp, health.armor), (70.0, 0.0));
+    }
+
+    #[test]
+    fn last_hit_point_kills_once() {
+        let mut health = Health::new(20.0);
+        assert_eq!(health.take(hit(25.0, DamageKind::Melee)), Hurt::Killed);
+        assert!(health.is_dead());
+        assert_eq!(health.hp, 0.0);
+        assert_eq!(health.take(hit(25.0, DamageKind::Melee)), Hurt::Ignored);
+    }
+
+    #[test]
+    fn heal_stops_at_max_and_not_when_dead() {
+        let mut health = Health::new(100.0);
+        assert!(!health.heal(25.0));
+        health.take(hit(10.0, DamageKind::Fire));
+        assert!(health.heal(25.0));
+        assert_eq!(health.hp, 100.0);
+        health.take(hit(500.0, DamageKind::Fire));
+        assert!(!health.heal(25.0));
+    }
+}
@@ -0,0 +1,666 @@
+//LEVEL FILES
+//levels are plain text so designers can edit them without recompiling
+//
+//  // comment lines start with two slashes
+//  tile # 1        legend: grid character -> wall or texture id (0 = empty floor)
+//  angle 90        player facing angle in degrees (90 = up)
+//  tile_size 64    optional world size of one tile in pixels
+//  floor 1         optional floor texture id for every tile (0 = none)
+//  ceiling 2       optional ceiling texture id for every tile (0 = none)
+//  ambient 0.4     optional light everywhere, 1.0 (the default) shows textures as they are
+//  light 2.5 3.5 4 1.0 0.8 0.5
+//                  point light at x y with a radius, all in tiles, and an r g b color
+//  pickup 3.5 1.5 health
+//                  something to collect at x y in tiles, drawn with the named sprite
+//  decoration 6.5 4.5 lamp
+//                  scenery at x y in tiles, drawn with the named sprite
+//  patrol 4 1 6 1 6 4
+//                  the enemy starting on tile 4 1 walks between tiles 6 1 and 6 4, over and over
+//  ranged 4 1 hitscan
+//                  how the enemy starting on tile 4 1 shoots, projectile (the default) or hitscan
+//  map             starts the grid, it runs until the next blank line
+//  ########
+//  #P..E..#        P = player start, E = enemy (both stand on empty floor)
+//  ########
+//
+//  floor_map       optional grids the same size as the map that set the floor or
+//  ceiling_map     ceiling texture of single tiles, using the same legend
+
+use crate::ai::Ranged;
+use crate::lighting::Light;
+use crate::map::{Map, DEFAULT_TILE_SIZE};
+use crate::parse::{expect_args, parse_arg, parse_err, LineError};
+use crate::world::{Decoration, Pickup};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+//grid characters reserved for placing things on the map
+const PLAYER_CHAR: char = 'P';
+const ENEMY_CHAR: char = 'E';
+
+//an enemy's start tile and the tiles it walks between
+pub type Patrol = ((usize, usize), Vec<(usize, usize)>);
+
+//an enemy's start tile and how it shoots
+pub type RangedEnemy = ((usize, usize), Ranged);
+
+//LEVEL STRUCTURE
+pub struct Level {
+    pub map: Map,                     //tile grid of the level
+    pub player_start: (usize, usize), //tile the player starts in
+    pub player_angle: f64,            //starting facing angle in degrees
+    pub enemies: Vec<(usize, usize)>, //tiles enemies start in
+    pub patrols: Vec<Patrol>,         //routes some of the enemies walk
+    pub ranged: Vec<RangedEnemy>,     //how each listed enemy shoots
+    pub ambient: f32,                 //light everywhere before point lights are added
+    pub lights: Vec<Light>,           //point lights in world coordinates
+    pub pickups: Vec<Pickup>,         //things to collect, in world coordinates
+    pub decorations: Vec<Decoration>, //scenery, in world coordinates
+}
+
+//ERRORS
+#[derive(Debug)]
+pub enum LevelError {
+    Io(io::Error),                      //file could not be read
+    Parse { line: usize, msg: String }, //file was read but is malformed
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelError::Io(e) => write!(f, "{}", e),
+            LevelError::Parse { line, msg } => write!(f, "line {}: {}", line, msg),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+impl From<io::Error> for LevelError {
+    fn from(e: io::Error) -> Self {
+        LevelError::Io(e)
+    }
+}
+
+impl From<LineError> for LevelError {
+    fn from((line, msg): LineError) -> Self {
+        LevelError::Parse { line, msg }
+    }
+}
+
+fn parse_texture_id(line: usize, arg: &str) -> Result<i32, LevelError> {
+    let id: i32 = parse_arg(line, "texture id", arg)?;
+    if id < 0 {
+        return Err(parse_err(
+            line,
+            format!("texture id must not be negative, got {}", id),
+        ));
+    }
+    Ok(id)
+}
+
+//which grid the rows being read belong to
+#[derive(Clone, Copy, PartialEq)]
+enum Grid {
+    Map,
+    Floor,
+    Ceiling,
+}
+
+impl Grid {
+    fn keyword(self) -> &'static str {
+        match self {
+            Grid::Map => "map",
+            Grid::Floor => "floor_map",
+            Grid::Ceiling => "ceiling_map",
+        }
+    }
+}
+
+//rows of one grid section with the line each came from
+struct GridRows<'a> {
+    start_line: usize,
+    rows: Vec<(usize, &'a str)>,
+}
+
+impl Level {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LevelError> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, LevelError> {
+        let mut legend: Vec<(char, i32)> = Vec::new();
+        let mut player_angle = 90.0;
+        let mut tile_size = DEFAULT_TILE_SIZE;
+        let mut floor_id = 0;
+        let mut ceiling_id = 0;
+        let mut ambient: f32 = 1.0;
+        let mut lights = Vec::new(); //in tiles until the tile size is known
+        let mut pickups: Vec<Pickup> = Vec::new(); //same
+        let mut decorations: Vec<Decoration> = Vec::new(); //same
+        let mut patrols = Vec::new(); //checked against the map once it is read
+        let mut ranged = Vec::new(); //same
+        let mut grids: Vec<(Grid, GridRows)> = Vec::new();
+        let mut in_grid = false; //true while reading rows of the last grid in grids
+
+        for (i, raw) in text.lines().enumerate() {
+            let line_no = i + 1; //editors count lines from 1
+            let line = raw.trim_end();
+
+            if in_grid {
+                //a blank line ends the grid
+                if line.is_empty() {
+                    in_grid = false;
+                } else if let Some((_, grid)) = grids.last_mut() {
+                    grid.rows.push((line_no, line));
+                }
+                continue;
+            }
+
+            let line = line.trim_start();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            let key = words.next().unwrap_or("");
+            let args: Vec<&str> = words.collect();
+
+            match key {
+                "tile" => {
+                    expect_args(line_no, &args, 2, "tile <char> <id>")?;
+                    let mut chars = args[0].chars();
+                    let c = match (chars.next(), chars.next()) {
+                        (Some(c), None) => c,
+                        _ => {
+                            return Err(parse_err(
+                                line_no,
+                                format!(
+                                    "tile character must be a single character, got `{}`",
+                                    args[0]
+                                ),
+                            ))
+                        }
+                    };
+                    if c == PLAYER_CHAR || c == ENEMY_CHAR {
+                        return Err(parse_err(
+                            line_no,
+                            format!("`{}` is reserved for spawn points", c),
+                        ));
+                    }
+                    if legend.iter().any(|&(l, _)| l == c) {
+                        return Err(parse_err(line_no, format!("tile `{}` is defined twice", c)));
+                    }
+                    let id: i32 = parse_arg(line_no, "tile id", args[1])?;
+                    if id < 0 {
+                        return Err(parse_err(
+                            line_no,
+                            format!("tile id must not be negative, got {}", id),
+                        ));
+                    }
+                    legend.push((c, id));
+                }
+                "angle" => {
+                    expect_args(line_no, &args, 1, "angle <degrees>")?;
+                    player_angle = parse_arg(line_no, "angle", args[0])?;
+                }
+                "tile_size" => {
+                    expect_args(line_no, &args, 1, "tile_size <pixels>")?;
+                    tile_size = parse_arg(line_no, "tile size", args[0])?;
+                    if !tile_size.is_finite() || tile_size <= 0.0 {
+                        return Err(parse_err(
+                            line_no,
+                            format!("tile size must be positive, got {}", tile_size),
+                        ));
+                    }
+                }
+                "floor" => {
+                    expect_args(line_no, &args, 1, "floor <texture id>")?;
+                    floor_id = parse_texture_id(line_no, args[0])?;
+                }
+                "ceiling" => {
+                    expect_args(line_no, &args, 1, "ceiling <texture id>")?;
+                    ceiling_id = parse_texture_id(line_no, args[0])?;
+                }
+                "ambient" => {
+                    expect_args(line_no, &args, 1, "ambient <brightness>")?;
+                    ambient = parse_arg(line_no, "brightness", args[0])?;
+                    if !ambient.is_finite() || ambient < 0.0 {
+                        return Err(parse_err(
+                            line_no,
+                            format!("ambient light must not be negative, got {}", ambient),
+                        ));
+                    }
+                }
+                "light" => {
+                    expect_args(line_no, &args, 6, "light <x> <y> <radius> <r> <g> <b>")?;
+                    let x: f64 = parse_arg(line_no, "light x", args[0])?;
+                    let y: f64 = parse_arg(line_no, "light y", args[1])?;
+                    let radius: f64 = parse_arg(line_no, "light radius", args[2])?;
+                    if !radius.is_finite() || radius <= 0.0 {
+                        return Err(parse_err(
+                            line_no,
+                            format!("light radius must be positive, got {}", radius),
+                        ));
+                    }
+                    let mut color = [0.0f32; 3];
+                    for (c, arg) in color.iter_mut().zip(&args[3..]) {
+                        *c = parse_arg(line_no, "light color", arg)?;
+                        if !c.is_finite() || *c < 0.0 {
+                            return Err(parse_err(
+                                line_no,
+                                format!("light color must not be negative, got {}", c),
+                            ));
+                        }
+                    }
+                    lights.push(Light {
+                        x,
+                        y,
+                        color,
+                        radius,
+                    });
+                }
+                "pickup" => {
+                    expect_args(line_no, &args, 3, "pickup <x> <y> <sprite>")?;
+                    pickups.push(Pickup {
+                        x: parse_arg(line_no, "pickup x", args[0])?,
+                        y: parse_arg(line_no, "pickup y", args[1])?,
+                        sprite: args[2].to_string(),
+                        taken: false,
+                    });
+                }
+                "decoration" => {
+                    expect_args(line_no, &args, 3, "decoration <x> <y> <sprite>")?;
+                    decorations.push(Decoration {
+                        x: parse_arg(line_no, "decoration x", args[0])?,
+                        y: parse_arg(line_no, "decoration y", args[1])?,
+                        sprite: args[2].to_string(),
+                    });
+                }
+                "patrol" => {
+                    if args.len() < 4 || !args.len().is_multiple_of(2) {
+                        return Err(parse_err(
+                            line_no,
+                            "expected `patrol <enemy x> <enemy y> <x> <y>...`".to_string(),
+                        ));
+                    }
+                    let tiles = args
+                        .chunks(2)
+                        .map(|pair| {
+                            let x: usize = parse_arg(line_no, "patrol x", pair[0])?;
+                            let y: usize = parse_arg(line_no, "patrol y", pair[1])?;
+                            Ok((x, y))
+                        })
+                        .collect::<Result<Vec<_>, LevelError>>()?;
+                    patrols.push((line_no, tiles[0], tiles[1..].to_vec()));
+                }
+                "ranged" => {
+                    expect_args(
+                        line_no,
+                        &args,
+                        3,
+                        "ranged <enemy x> <enemy y> <projectile|hitscan>",
+                    )?;
+                    let x: usize = parse_arg(line_no, "enemy x", args[0])?;
+                    let y: usize = parse_arg(line_no, "enemy y", args[1])?;
+                    let kind = match args[2] {
+                        "projectile" => Ranged::Projectile,
+                        "hitscan" => Ranged::Hitscan,
+                        other => {
+                            return Err(parse_err(
+                                line_no,
+                                format!("expected `projectile` or `hitscan`, got `{}`", other),
+                            ))
+                        }
+                    };
+                    ranged.push((line_no, (x, y), kind));
+                }
+                "map" | "floor_map" | "ceiling_map" => {
+                    let grid = match key {
+                        "map" => Grid::Map,
+                        "floor_map" => Grid::Floor,
+                        _ => Grid::Ceiling,
+                    };
+                    if !args.is_empty() {
+                        return Err(parse_err(line_no, format!("`{}` takes no arguments", key)));
+                    }
+                    if grids.iter().any(|(g, _)| *g == grid) {
+                        return Err(parse_err(line_no, format!("`{}` is given twice", key)));
+                    }
+                    grids.push((
+                        grid,
+                        GridRows {
+                            start_line: line_no,
+                            rows: Vec::new(),
+                        },
+                    ));
+                    in_grid = true;
+                }
+                _ => return Err(parse_err(line_no, format!("unknown keyword `{}`", key))),
+            }
+        }
+
+        for (grid, rows) in &grids {
+            if rows.rows.is_empty() {
+                return Err(parse_err(
+                    rows.start_line,
+                    format!("{} section is empty", grid.keyword()),
+                ));
+            }
+        }
+
+        let map_rows = match grids.iter().find(|(g, _)| *g == Grid::Map) {
+            Some((_, rows)) => rows,
+            None => {
+                return Err(parse_err(
+                    text.lines().count(),
+                    "missing `map` section".to_string(),
+                ))
+            }
+        };
+
+        let width = map_rows.rows[0].1.chars().count();
+        let height = map_rows.rows.len();
+        let mut tiles = Vec::with_capacity(width * height);
+        let mut player_start = None;
+        let mut enemies = Vec::new();
+
+        for (y, &(line_no, row)) in map_rows.rows.iter().enumerate() {
+            check_row_width(line_no, row, width)?;
+
+            for (x, c) in row.chars().enumerate() {
+                let tile = match c {
+                    PLAYER_CHAR => {
+                        if player_start.is_some() {
+                            return Err(parse_err(
+                                line_no,
+                                "more than one player start".to_string(),
+                            ));
+                        }
+                        player_start = Some((x, y));
+                        0
+                    }
+                    ENEMY_CHAR => {
+                        enemies.push((x, y));
+                        0
+                    }
+                    _ => legend_id(&legend, line_no, x, c)?,
+                };
+                tiles.push(tile);
+            }
+        }
+
+        let player_start = match player_start {
+            Some(p) => p,
+            None => {
+                return Err(parse_err(
+                    map_rows.start_line,
+                    format!("map has no player start (`{}`)", PLAYER_CHAR),
+                ))
+            }
+        };
+
+        //routes must belong to an enemy and only visit floor
+        for (line_no, start, route) in &patrols {
+            if !enemies.contains(start) {
+                return Err(parse_err(
+                    *line_no,
+                    format!("no enemy starts on tile {} {}", start.0, start.1),
+                ));
+            }
+            if patrols.iter().filter(|(_, s, _)| s == start).count() > 1 {
+                return Err(parse_err(
+                    *line_no,
+                    format!("enemy on tile {} {} has two routes", start.0, start.1),
+                ));
+            }
+            for &(x, y) in route {
+                if x >= width || y >= height || tiles[y * width + x] != 0 {
+                    return Err(parse_err(
+                        *line_no,
+                        format!("patrol tile {} {} is not open floor", x, y),
+                    ));
+                }
+            }
+        }
+        let patrols = patrols
+            .into_iter()
+            .map(|(_, start, route)| (start, route))
+            .collect();
+
+        //same for how enemies shoot
+        for (line_no, start, _) in &ranged {
+            if !enemies.contains(start) {
+                return Err(parse_err(
+                    *line_no,
+                    format!("no enemy starts on tile {} {}", start.0, start.1),
+                ));
+            }
+            if ranged.iter().filter(|(_, s, _)| s == start).count() > 1 {
+                return Err(parse_err(
+                    *line_no,
+                    format!(
+                        "enemy on tile {} {} has its ranged attack set twice",
+                        start.0, start.1
+                    ),
+                ));
+            }
+        }
+        let ranged = ranged
+            .into_iter()
+            .map(|(_, start, kind)| (start, kind))
+            .collect();
+
+        let mut map = Map::new(width, height, tile_size, tiles);
+        map.floor = vec![floor_id; width * height];
+        map.ceiling = vec![ceiling_id; width * height];
+
+        //per tile floor and ceiling textures override the defaults
+        for (grid, rows) in &grids {
+            let target = match grid {
+                Grid::Map => continue,
+                Grid::Floor => &mut map.floor,
+                Grid::Ceiling => &mut map.ceiling,
+            };
+            if rows.rows.len() != height {
+                return Err(parse_err(
+                    rows.start_line,
+                    format!(
+                        "{} has {} rows, the map has {}",
+                        grid.keyword(),
+                        rows.rows.len(),
+                        height
+                    ),
+                ));
+            }
+            for (y, &(line_no, row)) in rows.rows.iter().enumerate() {
+                check_row_width(line_no, row, width)?;
+                for (x, c) in row.chars().enumerate() {
+                    target[y * width + x] = legend_id(&legend, line_no, x, c)?;
+                }
+            }
+        }
+
+        //lights were given in tiles
+        for light in &mut lights {
+            light.x *= tile_size;
+            light.y *= tile_size;
+            light.radius *= tile_size;
+        }
+        for pickup in &mut pickups {
+            pickup.x *= tile_size;
+            pickup.y *= tile_size;
+        }
+        for decoration in &mut decorations {
+            decoration.x *= tile_size;
+            decoration.y *= tile_size;
+        }
+
+        Ok(Self {
+            map,
+            player_start,
+            player_angle,
+            enemies,
+            patrols,
+            ranged,
+            ambient,
+            lights,
+            pickups,
+            decorations,
+        })
+    }
+}
+
+fn check_row_width(line: usize, row: &str, width: usize) -> Result<(), LevelError> {
+    let count = row.chars().count();
+    if count != width {
+        return Err(parse_err(
+            line,
+            format!("row is {} tiles wide, expected {}", count, width),
+        ));
+    }
+    Ok(())
+}
+
+//looks up a grid character in the legend
+fn legend_id(legend: &[(char, i32)], line: usize, x: usize, c: char) -> Result<i32, LevelError> {
+    match legend.iter().find(|&&(l, _)| l == c) {
+        Some(&(_, id)) => Ok(id),
+        None => Err(parse_err(
+            line,
+            format!("unknown tile character `{}` in column {}", c, x + 1),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::line_error;
+
+    //line number and message of the error a level fails with
+    fn parse_error(text: &str) -> LineError {
+        line_error(Level::parse(text))
+    }
+
+    #[test]
+    fn loads_a_small_level() {
+        let level = Level::parse("tile # 1\nangle 0\nmap\n####\n#PE#\n####\n").unwrap();
+        assert_eq!((level.map.width, level.map.height), (4, 3));
+        assert_eq!(level.player_start, (1, 1));
+        assert_eq!(level.enemies, vec![(2, 1)]);
+        assert_eq!(level.player_angle, 0.0);
+        assert_eq!(level.map.tiles[0], 1);
+        assert_eq!(level.map.tiles[5], 0);
+    }
+
+    #[test]
+    fn ragged_rows() {
+        let text = "tile # 1\nmap\n####\n#P#\n####\n";
+        assert_eq!(
+            parse_error(text),
+            (4, "row is 3 tiles wide, expected 4".to_string())
+        );
+    }
+
+    #[test]
+    fn unknown_character() {
+        let text = "tile # 1\nmap\n####\n#P?#\n####\n";
+        assert_eq!(
+            parse_error(text),
+            (4, "unknown tile character `?` in column 3".to_string())
+        );
+    }
+
+    #[test]
+    fn missing_player_start() {
+        let text = "tile # 1\ntile . 0\nmap\n####\n#..#\n####\n";
+        assert_eq!(
+            parse_error(text),
+            (3, "map has no player start (`P`)".to_string())
+        );
+    }
+
+    #[test]
+    fn duplicate_player_start() {
+        let text = "tile # 1\ntile . 0\nmap\n####\n#P.#\n#.P#\n####\n";
+        assert_eq!(
+            parse_error(text),
+            (6, "more than one player start".to_string())
+        );
+    }
+
+    #[test]
+    fn duplicate_legend_entry() {
+        let text = "tile # 1\ntile # 2\nmap\n###\n#P#\n###\n";
+        assert_eq!(
+            parse_error(text),
+            (2, "tile `#` is defined twice".to_string())
+        );
+    }
+
+    #[test]
+    fn reserved_legend_characters() {
+        assert_eq!(
+            parse_error("tile P 1\n"),
+            (1, "`P` is reserved for spawn points".to_string())
+        );
+        assert_eq!(
+            parse_error("tile # 1\ntile E 2\n"),
+            (2, "`E` is reserved for spawn points".to_string())
+        );
+    }
+
+    #[test]
+    fn missing_map_section() {
+        let text = "tile # 1\nangle 90\n// no grid\n";
+        assert_eq!(parse_error(text), (3, "missing `map` section".to_string()));
+    }
+
+    #[test]
+    fn ranged_attacks_per_enemy() {
+        let text = "tile # 1\nranged 2 1 hitscan\nmap\n#####\n#PEE#\n#####\n";
+        let level = Level::parse(text).unwrap();
+        assert_eq!(level.ranged, vec![((2, 1), Ranged::Hitscan)]);
+
+        let text = "tile # 1\nranged 1 1 hitscan\nmap\n#####\n#PEE#\n#####\n";
+        assert_eq!(
+            parse_error(text),
+            (2, "no enemy starts on tile 1 1".to_string())
+        );
+        let text = "tile # 1\nranged 2 1 hitscan\nranged 2 1 projectile\nmap\n####\n#PE#\n####\n";
+        assert_eq!(
+            parse_error(text),
+            (
+                2,
+                "enemy on tile 2 1 has its ranged attack set twice".to_string()
+            )
+        );
+        assert_eq!(
+            parse_error("ranged 2 1 laser\n"),
+            (
+                1,
+                "expected `projectile` or `hitscan`, got `laser`".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn bad_keyword_arguments() {
+        assert_eq!(
+            parse_error("angle\n"),
+            (1, "expected `angle <degrees>`".to_string())
+        );
+        assert_eq!(
+            parse_error("tile_size -4\n"),
+            (1, "tile size must be positive, got -4".to_string())
+        );
+        assert_eq!(
+            parse_error("\nwalls 3\n"),
+            (2, "unknown keyword `walls`".to_string())
+        );
+    }
+}
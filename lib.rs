@@ -0,0 +1,26 @@
+//RUST RAYCASTER ENGINE
+//world, simulation and rendering as a library so other tools can load levels and run the game
+
+pub mod ai;
+pub mod animation;
+pub mod assets;
+pub mod damage;
+pub mod level;
+pub mod lighting;
+pub mod map;
+pub mod parse;
+pub mod pathfinding;
+pub mod projection;
+pub mod raycast;
+pub mod rng;
+pub mod screenshot;
+pub mod shading;
+pub mod sim;
+pub mod soft;
+pub mod textures;
+pub mod view;
+pub mod world;
+
+//drawing to a window needs piston_window, tools that only simulate can turn it off
+#[cfg(feature = "window")]
+pub mod render;
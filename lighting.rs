@@ -0,0 +1,276 @@
+//LIGHTING
+//point lights are baked into a light map with one color per floor tile and one per wall face
+//the renderers multiply texels by these colors, short lived lights like muzzle flashes are
+//added on top of the baked map every frame they are alive
+
+use crate::map::Map;
+use crate::raycast::{cast_ray_dir, Face};
+
+//light that leaves textures as they are
+pub const WHITE_LIGHT: [f32; 3] = [1.0, 1.0, 1.0];
+//lights can brighten a texture up to this much
+const MAX_LIGHT: f32 = 2.0;
+//wall faces are lit from this far in front of them so the wall itself doesn't block the light
+const FACE_OFFSET: f64 = 1.0;
+
+//muzzle flash: warm, a few tiles wide and gone in a tenth of a second
+pub const MUZZLE_FLASH_COLOR: [f32; 3] = [1.0, 0.8, 0.45];
+pub const MUZZLE_FLASH_RADIUS: f64 = 3.0; //in tiles
+pub const MUZZLE_FLASH_TIME: f64 = 0.1; //seconds
+
+//POINT LIGHT
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    pub x: f64,          //world position
+    pub y: f64,          //world position
+    pub color: [f32; 3], //color at full strength, 1.0 is as bright as the texture
+    pub radius: f64,     //world distance where the light fades out completely
+}
+
+impl Light {
+    //how much of the light reaches a point, 0.0 to 1.0, ignoring walls in the way
+    fn falloff(&self, x: f64, y: f64) -> f64 {
+        let d = ((x - self.x).powi(2) + (y - self.y).powi(2)).sqrt();
+        if d >= self.radius {
+            return 0.0;
+        }
+        let t = 1.0 - d / self.radius;
+        t * t
+    }
+
+    //falloff with walls blocking the light
+    fn reaching(&self, map: &Map, x: f64, y: f64) -> f64 {
+        let strength = self.falloff(x, y);
+        if strength <= 0.0 {
+            return 0.0;
+        }
+        let (dx, dy) = (x - self.x, y - self.y);
+        let d = (dx * dx + dy * dy).sqrt();
+        if d > 0.0 {
+            if let Some(hit) = cast_ray_dir(map, (self.x, self.y), (dx / d, dy / d)) {
+                if hit.distance < d {
+                    return 0.0; //a wall is in the way
+                }
+            }
+        }
+        strength
+    }
+}
+
+//light that is only there for a short time, fading out as it goes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Flash {
+    pub light: Light,
+    pub time_left: f64, //seconds
+    pub duration: f64,  //seconds it started with
+}
+
+impl Flash {
+    pub fn muzzle(x: f64, y: f64, tile_size: f64) -> Self {
+        Self {
+            light: Light {
+                x,
+                y,
+                color: MUZZLE_FLASH_COLOR,
+                radius: MUZZLE_FLASH_RADIUS * tile_size,
+            },
+            time_left: MUZZLE_FLASH_TIME,
+            duration: MUZZLE_FLASH_TIME,
+        }
+    }
+
+    //1.0 when it starts, 0.0 when it is gone
+    pub fn strength(&self) -> f64 {
+        (self.time_left / self.duration).clamp(0.0, 1.0)
+    }
+}
+
+fn add_scaled(to: &mut [f32; 3], color: [f32; 3], k: f64) {
+    for (c, l) in to.iter_mut().zip(color) {
+        *c = (*c + l * k as f32).min(MAX_LIGHT);
+    }
+}
+
+//LIGHT MAP
+#[derive(Clone, Debug, PartialEq)]
+pub struct LightMap {
+    width: usize,
+    height: usize,
+    ambient: [f32; 3],         //light everywhere, also used outside the map
+    tiles: Vec<[f32; 3]>,      //light on the floor and ceiling of each tile
+    faces: Vec<[[f32; 3]; 4]>, //light on each face of each tile, indexed by Face::index
+}
+
+impl LightMap {
+    //every tile and face lit by ambient light only
+    pub fn new(map: &Map, ambient: [f32; 3]) -> Self {
+        let n = map.width * map.height;
+        Self {
+            width: map.width,
+            height: map.height,
+            ambient,
+            tiles: vec![ambient; n],
+            faces: vec![[ambient; 4]; n],
+        }
+    }
+
+    //ambient light plus every light, with walls casting shadows
+    pub fn bake(map: &Map, ambient: [f32; 3], lights: &[Light]) -> Self {
+        let mut light_map = Self::new(map, ambient);
+        for light in lights {
+            light_map.add_light(map, light, 1.0);
+        }
+        light_map
+    }
+
+    //adds one light at strength k (0.0 to 1.0) to the tiles and faces it reaches
+    pub fn add_light(&mut self, map: &Map, light: &Light, k: f64) {
+        let ts = map.tile_size;
+        let reach = (light.radius / ts).ceil() as i32 + 1; //tiles further away than this can't be lit
+        let (lx, ly) = map.to_tile(light.x, light.y);
+
+        for ty in (ly - reach).max(0)..(ly + reach + 1).min(self.height as i32) {
+            for tx in (lx - reach).max(0)..(lx + reach + 1).min(self.width as i32) {
+                let i = ty as usize * self.width + tx as usize;
+
+                if !map.is_wall(tx, ty) {
+                    let (cx, cy) = map.tile_center(tx as usize, ty as usize);
+                    let s = light.reaching(map, cx, cy);
+                    if s > 0.0 {
+                        add_scaled(&mut self.tiles[i], light.color, s * k);
+                    }
+                    continue;
+                }
+
+                //walls are lit face by face, from just in front of the middle of each face
+                for face in [Face::North, Face::East, Face::South, Face::West] {
+                    let (nx, ny) = face.normal();
+                    if map.is_wall(tx + nx, ty + ny) {
+                        continue; //face is buried against another wall
+                    }
+                    let (cx, cy) = map.tile_center(tx as usize, ty as usize);
+                    let px = cx + nx as f64 * (ts / 2.0 + FACE_OFFSET);
+                    let py = cy + ny as f64 * (ts / 2.0 + FACE_OFFSET);
+                    let s = light.reaching(map, px, py);
+                    if s > 0.0 {
+                        add_scaled(&mut self.faces[i][face.index()], light.color, s * k);
+                    }
+                }
+            }
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+        Some(y as usize * self.width + x as usize)
+    }
+
+    //light on the floor and ceiling of tile (x, y)
+    pub fn tile(&self, x: i32, y: i32) -> [f32; 3] {
+        self.index(x, y).map_or(self.ambient, |i| self.tiles[i])
+    }
+
+    //light on one face of wall tile (x, y)
+    pub fn face(&self, x: i32, y: i32, face: Face) -> [f32; 3] {
+        self.index(x, y)
+            .map_or(self.ambient, |i| self.faces[i][face.index()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AMBIENT: [f32; 3] = [0.2, 0.2, 0.2];
+
+    //builds a map from rows of '#' walls and '.' floor, tiles 64 wide
+    fn build_map(rows: &[&str]) -> Map {
+        let tiles = rows
+            .iter()
+            .flat_map(|row| row.chars().map(|c| if c == '#' { 1 } else { 0 }))
+            .collect();
+        Map::new(rows[0].len(), rows.len(), 64.0, tiles)
+    }
+
+    //two rooms side by side with a wall down column 3
+    fn two_rooms() -> Map {
+        build_map(&["#######", "#..#..#", "#..#..#", "#######"])
+    }
+
+    //white light in the middle of tile (x, y), radius in tiles
+    fn white(map: &Map, x: usize, y: usize, radius: f64) -> Light {
+        let (x, y) = map.tile_center(x, y);
+        Light {
+            x,
+            y,
+            color: WHITE_LIGHT,
+            radius: radius * map.tile_size,
+        }
+    }
+
+    #[test]
+    fn walls_block_light() {
+        let map = two_rooms();
+        let light_map = LightMap::bake(&map, AMBIENT, &[white(&map, 1, 1, 4.0)]);
+
+        //one tile away three quarters of the way out, squared
+        assert_eq!(light_map.tile(2, 1), [0.2 + 0.5625; 3]);
+        //closer than the radius but behind the wall
+        assert_eq!(light_map.tile(4, 1), AMBIENT);
+
+        //the wall is lit on the side facing the light and not on the far side
+        assert!(light_map.face(3, 1, Face::West)[0] > AMBIENT[0]);
+        assert_eq!(light_map.face(3, 1, Face::East), AMBIENT);
+    }
+
+    #[test]
+    fn light_stops_at_its_radius() {
+        let map = two_rooms();
+        let light_map = LightMap::bake(&map, AMBIENT, &[white(&map, 1, 1, 1.0)]);
+        assert_eq!(light_map.tile(2, 1), AMBIENT);
+
+        let light_map = LightMap::bake(&map, AMBIENT, &[white(&map, 1, 1, 1.1)]);
+        assert!(light_map.tile(2, 1)[0] > AMBIENT[0]);
+    }
+
+    #[test]
+    fn faces_against_another_wall_stay_unlit() {
+        let map = two_rooms();
+        let light_map = LightMap::bake(&map, AMBIENT, &[white(&map, 1, 1, 4.0)]);
+        //the wall left of the light faces it to the east and the next wall down to the south
+        assert!(light_map.face(0, 1, Face::East)[0] > AMBIENT[0]);
+        assert_eq!(light_map.face(0, 1, Face::South), AMBIENT);
+    }
+
+    #[test]
+    fn light_adds_up_to_the_maximum() {
+        let map = two_rooms();
+        let lights = vec![white(&map, 1, 1, 4.0); 10];
+        let light_map = LightMap::bake(&map, AMBIENT, &lights);
+        assert_eq!(light_map.tile(1, 1), [MAX_LIGHT; 3]);
+        assert_eq!(light_map.face(0, 1, Face::East), [MAX_LIGHT; 3]);
+    }
+
+    #[test]
+    fn flashes_fade_out() {
+        let map = two_rooms();
+        let (x, y) = map.tile_center(1, 1);
+        let mut flash = Flash::muzzle(x, y, map.tile_size);
+        assert_eq!(flash.strength(), 1.0);
+
+        flash.time_left = MUZZLE_FLASH_TIME / 2.0;
+        assert_eq!(flash.strength(), 0.5);
+        let mut light_map = LightMap::new(&map, AMBIENT);
+        light_map.add_light(&map, &flash.light, flash.strength());
+        assert_eq!(
+            light_map.tile(1, 1),
+            [0.2 + 0.5, 0.2 + 0.4, 0.2 + 0.225],
+            "half of the muzzle flash color"
+        );
+
+        flash.time_left = -0.01;
+        assert_eq!(flash.strength(), 0.0);
+    }
+}
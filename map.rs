@@ -0,0 +1,95 @@
+//MAP STRUCTURE
+//tile grid of any size, tiles are square and tile_size pixels wide in world space
+
+pub const DEFAULT_TILE_SIZE: f64 = 64.0; //each map cube size in pixels unless the level says otherwise
+
+pub struct Map {
+    pub width: usize,      //number of tiles across
+    pub height: usize,     //number of tiles down
+    pub tile_size: f64,    //world size of one tile in pixels
+    pub tiles: Vec<i32>,   //wall id per tile, row by row (0 = empty)
+    pub floor: Vec<i32>,   //floor texture id per tile (0 = none)
+    pub ceiling: Vec<i32>, //ceiling texture id per tile (0 = none)
+}
+
+impl Map {
+    pub fn new(width: usize, height: usize, tile_size: f64, tiles: Vec<i32>) -> Self {
+        assert_eq!(
+            tiles.len(),
+            width * height,
+            "tile count does not match map size"
+        );
+        assert!(tile_size > 0.0, "tile size must be positive");
+        Self {
+            width,
+            height,
+            tile_size,
+            tiles,
+            floor: vec![0; width * height],
+            ceiling: vec![0; width * height],
+        }
+    }
+
+    //wall id at tile (x, y), None if the tile is outside the map
+    pub fn tile(&self, x: i32, y: i32) -> Option<i32> {
+        self.index(x, y).map(|i| self.tiles[i])
+    }
+
+    //position of tile (x, y) in the tile vectors, None if it is outside the map
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+        Some(y as usize * self.width + x as usize)
+    }
+
+    //floor and ceiling texture ids at tile (x, y), None outside the map
+    pub fn floor(&self, x: i32, y: i32) -> Option<i32> {
+        self.index(x, y).map(|i| self.floor[i])
+    }
+
+    pub fn ceiling(&self, x: i32, y: i32) -> Option<i32> {
+        self.index(x, y).map(|i| self.ceiling[i])
+    }
+
+    //anything outside the map counts as solid so nothing can walk off the edge
+    pub fn is_wall(&self, x: i32, y: i32) -> bool {
+        self.tile(x, y) != Some(0)
+    }
+
+    //converts a world position to the tile it is in
+    pub fn to_tile(&self, wx: f64, wy: f64) -> (i32, i32) {
+        (
+            (wx / self.tile_size).floor() as i32,
+            (wy / self.tile_size).floor() as i32,
+        )
+    }
+
+    //wall id at a world position
+    pub fn tile_at(&self, wx: f64, wy: f64) -> Option<i32> {
+        let (x, y) = self.to_tile(wx, wy);
+        self.tile(x, y)
+    }
+
+    pub fn is_wall_at(&self, wx: f64, wy: f64) -> bool {
+        let (x, y) = self.to_tile(wx, wy);
+        self.is_wall(x, y)
+    }
+
+    //world position of the center of tile (x, y)
+    pub fn tile_center(&self, x: usize, y: usize) -> (f64, f64) {
+        (
+            x as f64 * self.tile_size + self.tile_size / 2.0,
+            y as f64 * self.tile_size + self.tile_size / 2.0,
+        )
+    }
+
+    //size of the whole map in world pixels
+    pub fn world_width(&self) -> f64 {
+        self.width as f64 * self.tile_size
+    }
+
+    pub fn world_height(&self) -> f64 {
+        self.height as f64 * self.tile_size
+    }
+}
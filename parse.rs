@@ -0,0 +1,45 @@
+//LINE PARSING
+//helpers shared by the plain text file formats, levels, texture manifests and sprite sheets are
+//read a line at a time as a keyword followed by arguments
+//
+//errors come back as the line they are on and a message, each format turns that into its own
+//error type with From so they can be passed on with ?
+
+use std::str::FromStr;
+
+//line number (counting from 1) and what is wrong with it
+pub type LineError = (usize, String);
+
+//an error of any format that can be built from a LineError
+pub fn parse_err<E: From<LineError>>(line: usize, msg: String) -> E {
+    E::from((line, msg))
+}
+
+//checks a keyword got the right number of arguments
+pub fn expect_args(line: usize, args: &[&str], count: usize, usage: &str) -> Result<(), LineError> {
+    if args.len() != count {
+        return Err((line, format!("expected `{}`", usage)));
+    }
+    Ok(())
+}
+
+//parses one argument, what names it in the error message
+pub fn parse_arg<T: FromStr>(line: usize, what: &str, arg: &str) -> Result<T, LineError> {
+    arg.parse()
+        .map_err(|_| (line, format!("bad {} `{}`", what, arg)))
+}
+
+//line number and message of the parse error a file failed with, for the tests of each format
+//every format shows parse errors as `line <n>: <msg>`
+#[cfg(test)]
+pub fn line_error<T, E: std::fmt::Display>(result: Result<T, E>) -> LineError {
+    let shown = match result {
+        Err(e) => e.to_string(),
+        Ok(_) => panic!("expected a parse error, the file loaded"),
+    };
+    shown
+        .strip_prefix("line ")
+        .and_then(|rest| rest.split_once(": "))
+        .and_then(|(line, msg)| Some((line.parse().ok()?, msg.to_string())))
+        .unwrap_or_else(|| panic!("expected a parse error, got {}", shown))
+}
@@ -0,0 +1,278 @@
+//PATHFINDING
+//A* over the tile grid so enemies can find their way round walls
+//
+//moves go to any of the 8 neighbouring tiles, diagonals can't cut corners, both tiles beside
+//a diagonal step must be open too
+
+use crate::map::Map;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+//move costs, whole numbers so the open list can be ordered exactly
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14; //about 10 * sqrt(2)
+
+//the 8 neighbouring tiles, straight ones first so ties prefer them
+const STEPS: [(i32, i32); 8] = [
+    (1, 0),
+    (0, 1),
+    (-1, 0),
+    (0, -1),
+    (1, 1),
+    (-1, 1),
+    (-1, -1),
+    (1, -1),
+];
+
+//what is in a tile as far as walking goes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cell {
+    Open,
+    Wall,
+}
+
+impl Cell {
+    fn passable(self) -> bool {
+        self == Cell::Open
+    }
+}
+
+//NAVIGATION GRID
+//walkability of every tile, built from the map's walls
+pub struct NavGrid {
+    pub width: usize,
+    pub height: usize,
+    cells: Vec<Cell>, //row by row like the map's tiles
+}
+
+impl NavGrid {
+    pub fn from_map(map: &Map) -> Self {
+        let cells = map
+            .tiles
+            .iter()
+            .map(|&id| if id == 0 { Cell::Open } else { Cell::Wall })
+            .collect();
+        Self {
+            width: map.width,
+            height: map.height,
+            cells,
+        }
+    }
+
+    //anything outside the grid counts as a wall
+    pub fn cell(&self, x: i32, y: i32) -> Cell {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return Cell::Wall;
+        }
+        self.cells[y as usize * self.width + x as usize]
+    }
+
+    //cost of stepping from a tile by (dx, dy), None if the step is not allowed
+    fn step_cost(&self, from: (i32, i32), (dx, dy): (i32, i32)) -> Option<u32> {
+        let to = self.cell(from.0 + dx, from.1 + dy);
+        if !to.passable() {
+            return None;
+        }
+        if dx != 0 && dy != 0 {
+            let side_a = self.cell(from.0 + dx, from.1);
+            let side_b = self.cell(from.0, from.1 + dy);
+            if !side_a.passable() || !side_b.passable() {
+                return None; //would cut a corner
+            }
+            return Some(DIAGONAL_COST);
+        }
+        Some(STRAIGHT_COST)
+    }
+
+    //tiles to walk through to get from start to goal, not including start
+    //empty if they are the same tile, None if goal can't be reached
+    pub fn find_path(&self, start: (i32, i32), goal: (i32, i32)) -> Option<Vec<(i32, i32)>> {
+        if !self.cell(goal.0, goal.1).passable() {
+            return None;
+        }
+        if start == goal {
+            return Some(Vec::new());
+        }
+        //start does not have to be passable but it has to be on the grid
+        if start.0 < 0
+            || start.1 < 0
+            || start.0 >= self.width as i32
+            || start.1 >= self.height as i32
+        {
+            return None;
+        }
+
+        let index = |(x, y): (i32, i32)| y as usize * self.width + x as usize;
+        let mut cost_so_far = vec![u32::MAX; self.cells.len()];
+        let mut came_from: Vec<Option<(i32, i32)>> = vec![None; self.cells.len()];
+        //open list ordered by estimated total cost, then by insertion order so results are stable
+        let mut open = BinaryHeap::new();
+        let mut pushed = 0u32;
+
+        cost_so_far[index(start)] = 0;
+        open.push(Reverse((heuristic(start, goal), pushed, start)));
+
+        while let Some(Reverse((_, _, current))) = open.pop() {
+            if current == goal {
+                break;
+            }
+            let current_cost = cost_so_far[index(current)];
+            for step in STEPS {
+                let cost = match self.step_cost(current, step) {
+                    Some(cost) => current_cost + cost,
+                    None => continue,
+                };
+                let next = (current.0 + step.0, current.1 + step.1);
+                if cost < cost_so_far[index(next)] {
+                    cost_so_far[index(next)] = cost;
+                    came_from[index(next)] = Some(current);
+                    pushed += 1;
+                    open.push(Reverse((cost + heuristic(next, goal), pushed, next)));
+                }
+            }
+        }
+
+        came_from[index(goal)]?; //the goal was never reached
+
+        //walk back from the goal to the start
+        let mut path = vec![goal];
+        let mut tile = goal;
+        while let Some(prev) = came_from[index(tile)] {
+            if prev == start {
+                break;
+            }
+            path.push(prev);
+            tile = prev;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    //world position to walk towards next on the way from one world position to another
+    //the middle of the next tile on the path, or the target itself once in its tile
+    //None if the target can't be reached
+    pub fn next_waypoint(&self, map: &Map, from: (f64, f64), to: (f64, f64)) -> Option<(f64, f64)> {
+        let start = map.to_tile(from.0, from.1);
+        let goal = map.to_tile(to.0, to.1);
+        let path = self.find_path(start, goal)?;
+        match path.first() {
+            Some(&(x, y)) => Some(map.tile_center(x as usize, y as usize)),
+            None => Some(to),
+        }
+    }
+}
+
+//octile distance, the cost of the best path if there were no walls
+fn heuristic(a: (i32, i32), b: (i32, i32)) -> u32 {
+    let dx = (a.0 - b.0).unsigned_abs();
+    let dy = (a.1 - b.1).unsigned_abs();
+    STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //builds a grid from rows of '#' walls and '.' floor
+    fn grid(rows: &[&str]) -> NavGrid {
+        let width = rows[0].len();
+        let mut tiles = Vec::new();
+        for row in rows {
+            for c in row.chars() {
+                tiles.push(if c == '#' { 1 } else { 0 });
+            }
+        }
+        NavGrid::from_map(&Map::new(width, rows.len(), 64.0, tiles))
+    }
+
+    //total cost of walking a path from start
+    fn path_cost(nav: &NavGrid, start: (i32, i32), path: &[(i32, i32)]) -> u32 {
+        let mut cost = 0;
+        let mut from = start;
+        for &to in path {
+            cost += nav.step_cost(from, (to.0 - from.0, to.1 - from.1)).unwrap();
+            from = to;
+        }
+        cost
+    }
+
+    #[test]
+    fn straight_line_in_open_room() {
+        let nav = grid(&["#######", "#.....#", "#######"]);
+        let path = nav.find_path((1, 1), (5, 1)).unwrap();
+        assert_eq!(path, vec![(2, 1), (3, 1), (4, 1), (5, 1)]);
+    }
+
+    #[test]
+    fn same_tile_is_an_empty_path() {
+        let nav = grid(&["###", "#.#", "###"]);
+        assert_eq!(nav.find_path((1, 1), (1, 1)), Some(Vec::new()));
+    }
+
+    #[test]
+    fn goes_round_a_wall() {
+        let nav = grid(&[
+            "#######", //
+            "#..#..#", "#..#..#", "#.....#", "#######",
+        ]);
+        let path = nav.find_path((1, 1), (5, 1)).unwrap();
+        assert_eq!(path.last(), Some(&(5, 1)));
+        assert!(path.iter().all(|&(x, y)| nav.cell(x, y) == Cell::Open));
+        assert!(
+            path.contains(&(3, 3)),
+            "must pass under the wall: {:?}",
+            path
+        );
+        //diagonal to (2, 2), down, along under the wall to (4, 3), diagonal up to (5, 2), up
+        assert_eq!(path_cost(&nav, (1, 1), &path), 68);
+    }
+
+    #[test]
+    fn takes_diagonals_in_the_open() {
+        let nav = grid(&["#####", "#...#", "#...#", "#...#", "#####"]);
+        let path = nav.find_path((1, 1), (3, 3)).unwrap();
+        assert_eq!(path, vec![(2, 2), (3, 3)]);
+    }
+
+    #[test]
+    fn does_not_cut_corners() {
+        //the only diagonal from (1, 2) to (2, 1) squeezes past the wall at (1, 1)
+        let nav = grid(&["####", "##.#", "#..#", "####"]);
+        let path = nav.find_path((1, 2), (2, 1)).unwrap();
+        assert_eq!(path, vec![(2, 2), (2, 1)]);
+    }
+
+    #[test]
+    fn no_path_through_diagonal_gap() {
+        //two open tiles touching only at a corner are not connected
+        let nav = grid(&["####", "#.##", "##.#", "####"]);
+        assert_eq!(nav.find_path((1, 1), (2, 2)), None);
+    }
+
+    #[test]
+    fn unreachable_and_solid_goals() {
+        let nav = grid(&["#####", "#.#.#", "#####"]);
+        assert_eq!(nav.find_path((1, 1), (3, 1)), None);
+        assert_eq!(nav.find_path((1, 1), (2, 1)), None); //goal is a wall
+        assert_eq!(nav.find_path((1, 1), (9, 9)), None); //goal is off the map
+    }
+
+    #[test]
+    fn next_waypoint_is_the_middle_of_the_next_tile() {
+        let rows = ["#####", "#...#", "###.#", "#...#", "#####"];
+        let nav = grid(&rows);
+        let tiles = rows
+            .iter()
+            .flat_map(|row| row.chars().map(|c| if c == '#' { 1 } else { 0 }))
+            .collect();
+        let map = Map::new(5, 5, 64.0, tiles);
+
+        //from tile (1, 1) to tile (1, 3) the way is east along the top first
+        let next = nav.next_waypoint(&map, (80.0, 90.0), (100.0, 220.0));
+        assert_eq!(next, Some((160.0, 96.0)));
+
+        //already in the target's tile, head straight for the target
+        let next = nav.next_waypoint(&map, (70.0, 70.0), (100.0, 110.0));
+        assert_eq!(next, Some((100.0, 110.0)));
+    }
+}
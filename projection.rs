@@ -0,0 +1,66 @@
+//PROJECTION
+//the one place world positions and distances turn into screen positions and sizes
+//walls, floors, ceilings and sprites all go through here so they agree on how far away things are
+//
+//depth is always the perpendicular distance along the view direction, never the straight line
+//distance, so nothing bulges at the edges of the view (no fisheye) and anything at the same depth
+//is drawn at the same scale
+//the eye is half a tile above the floor, so the horizon is the middle of the view
+
+use crate::view::Camera;
+use crate::world::Player;
+
+//points closer than this in front of the eye are not drawn (avoids dividing by almost 0)
+pub const NEAR_DEPTH: f64 = 1.0;
+
+//on screen height of something one tile tall at this depth
+pub fn wall_height(camera: &Camera, tile_size: f64, depth: f64) -> f64 {
+    (tile_size * camera.focal_length()) / depth //nearby walls produce tall columns, far produce short
+}
+
+//screen y of the horizon, where the eye looks straight ahead
+pub fn horizon(camera: &Camera) -> f64 {
+    camera.height / 2.0
+}
+
+//screen y of a point at this depth and height above the floor (in world units)
+pub fn screen_y(camera: &Camera, tile_size: f64, depth: f64, above_floor: f64) -> f64 {
+    let below_eye = tile_size / 2.0 - above_floor;
+    horizon(camera) + below_eye * camera.focal_length() / depth
+}
+
+//depth of the floor (or ceiling) seen by a row of pixels this far from the horizon
+//this is the depth where a wall's bottom (or top) edge would be drawn at that row
+pub fn row_distance(camera: &Camera, tile_size: f64, pixels_from_horizon: f64) -> f64 {
+    (tile_size * camera.focal_length() / 2.0) / pixels_from_horizon
+}
+
+//a world point as seen by the camera
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Projected {
+    pub x: f64,     //screen x, can be off either side of the view
+    pub depth: f64, //perpendicular distance in front of the eye
+}
+
+//where a world point lands on screen, None if it is behind the eye or too close to see
+//points just outside the view still come back so sprites can hang in from the edges
+pub fn project_point(player: &Player, camera: &Camera, x: f64, y: f64) -> Option<Projected> {
+    let vx = x - player.px; //vector from player to the point
+    let vy = y - player.py; //vector from player to the point
+
+    //split the vector into how far ahead and how far to the right of the player the point is
+    let ahead = player.pdx * vx + player.pdy * vy;
+    let right = -player.pdy * vx + player.pdx * vy;
+    if ahead < NEAR_DEPTH {
+        return None; //behind the camera or right on top of it
+    }
+
+    //the ray through the point crosses the camera plane at right / ahead
+    let (plane_x, plane_y) = camera.plane(player);
+    let plane_len = (plane_x * plane_x + plane_y * plane_y).sqrt();
+    let norm = (right / ahead / plane_len + 1.0) / 2.0; //0.0 left edge, 1.0 right edge
+    Some(Projected {
+        x: norm * camera.width,
+        depth: ahead,
+    })
+}
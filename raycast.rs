@@ -0,0 +1,280 @@
+//RAY CASTING
+//grid traversal (DDA) shared by rendering and gameplay, it only needs a map so it runs without a window
+
+use crate::map::Map;
+
+//which kind of grid line the ray crossed when it hit the wall
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Vertical,   //crossed a vertical grid line (east or west face of a tile)
+    Horizontal, //crossed a horizontal grid line (north or south face of a tile)
+}
+
+//which face of the wall tile the ray hit, named by the direction it faces (north is up)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Face {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Face {
+    //position in per face tables
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    //tile step from the wall tile to the tile this face looks into
+    pub fn normal(self) -> (i32, i32) {
+        match self {
+            Face::North => (0, -1),
+            Face::East => (1, 0),
+            Face::South => (0, 1),
+            Face::West => (-1, 0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    pub distance: f64, //from the origin to the hit, in lengths of the ray direction
+    pub hit_point: (f64, f64), //world position where the ray hit the wall
+    pub tile: i32,     //wall id of the tile that was hit
+    pub side: Side,    //which kind of grid line the hit is on
+    pub face: Face,    //which face of the tile was hit
+    pub cell: (i32, i32), //tile coordinates of the wall that was hit
+    pub tex_u: f64,    //how far along the wall face the hit is, 0.0 to 1.0
+}
+
+//casts a ray from origin at angle (degrees, 90 is up) until it hits a wall
+//returns None if the ray leaves the map without hitting anything
+pub fn cast_ray(map: &Map, origin: (f64, f64), angle: f64) -> Option<RayHit> {
+    let rad = angle.to_radians();
+    cast_ray_dir(map, origin, (rad.cos(), -rad.sin())) //negative because y increases down
+}
+
+//same as cast_ray but takes a direction vector instead of an angle
+//dir does not have to be unit length, distance is then measured in multiples of it
+//(a camera ray gives the perpendicular distance to the wall this way)
+pub fn cast_ray_dir(map: &Map, origin: (f64, f64), dir: (f64, f64)) -> Option<RayHit> {
+    let (ox, oy) = origin;
+    let (dx, dy) = dir;
+    if dx == 0.0 && dy == 0.0 {
+        return None; //no direction to cast in
+    }
+    let ts = map.tile_size;
+    let (mut mx, mut my) = map.to_tile(ox, oy); //tile the ray is currently in
+
+    //distance along the ray to cross one whole tile in x and in y
+    let delta_x = if dx == 0.0 {
+        f64::INFINITY
+    } else {
+        (ts / dx).abs()
+    };
+    let delta_y = if dy == 0.0 {
+        f64::INFINITY
+    } else {
+        (ts / dy).abs()
+    };
+
+    //which way to step through the grid and the distance to the first grid line in each direction
+    let (step_x, mut side_x) = if dx < 0.0 {
+        (-1, (ox - mx as f64 * ts) / -dx)
+    } else if dx > 0.0 {
+        (1, ((mx + 1) as f64 * ts - ox) / dx)
+    } else {
+        (0, f64::INFINITY)
+    };
+    let (step_y, mut side_y) = if dy < 0.0 {
+        (-1, (oy - my as f64 * ts) / -dy)
+    } else if dy > 0.0 {
+        (1, ((my + 1) as f64 * ts - oy) / dy)
+    } else {
+        (0, f64::INFINITY)
+    };
+
+    //step to whichever grid line is closer until a wall is found or the ray leaves the map
+    loop {
+        let (distance, side) = if side_x < side_y {
+            let d = side_x;
+            side_x += delta_x;
+            mx += step_x;
+            (d, Side::Vertical)
+        } else {
+            let d = side_y;
+            side_y += delta_y;
+            my += step_y;
+            (d, Side::Horizontal)
+        };
+
+        match map.tile(mx, my) {
+            None => return None,
+            Some(0) => continue,
+            Some(tile) => {
+                let hit_point = (ox + dx * distance, oy + dy * distance);
+                let along = match side {
+                    Side::Vertical => hit_point.1,
+                    Side::Horizontal => hit_point.0,
+                };
+                //a ray moving right hits the west face of a tile, and so on
+                let face = match side {
+                    Side::Vertical if step_x > 0 => Face::West,
+                    Side::Vertical => Face::East,
+                    Side::Horizontal if step_y > 0 => Face::North,
+                    Side::Horizontal => Face::South,
+                };
+                return Some(RayHit {
+                    distance,
+                    hit_point,
+                    tile,
+                    side,
+                    face,
+                    cell: (mx, my),
+                    tex_u: along.rem_euclid(ts) / ts,
+                });
+            }
+        }
+    }
+}
+
+//true if nothing solid is in the way on the straight line from a to b (world positions)
+//walks the same grid as the rays, so what an enemy can see or a shot can hit matches what is drawn
+//the tile a is in is not checked, a point inside a wall can see out of it but not into another
+pub fn has_line_of_sight(map: &Map, a: (f64, f64), b: (f64, f64)) -> bool {
+    //with the whole offset as the direction, a wall before b is hit at a distance below 1
+    let dir = (b.0 - a.0, b.1 - a.1);
+    match cast_ray_dir(map, a, dir) {
+        Some(hit) => hit.distance >= 1.0,
+        None => map.tile_at(b.0, b.1).is_some(), //the ray left the map, fine unless b is off it too
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //5x5 room with one wall tile in the middle, tiles 64 wide
+    fn room() -> Map {
+        #[rustfmt::skip]
+        let tiles = vec![
+            1, 1, 1, 1, 1,
+            1, 0, 0, 0, 1,
+            1, 0, 2, 0, 1,
+            1, 0, 0, 0, 1,
+            1, 1, 1, 1, 1,
+        ];
+        Map::new(5, 5, 64.0, tiles)
+    }
+
+    #[test]
+    fn straight_east_hits_the_west_face() {
+        let map = room();
+        let hit = cast_ray_dir(&map, (96.0, 96.0), (1.0, 0.0)).unwrap();
+        assert_eq!(hit.distance, 160.0);
+        assert_eq!(hit.hit_point, (256.0, 96.0));
+        assert_eq!((hit.tile, hit.cell), (1, (4, 1)));
+        assert_eq!((hit.side, hit.face), (Side::Vertical, Face::West));
+        assert_eq!(hit.tex_u, 0.5);
+    }
+
+    #[test]
+    fn straight_west_hits_the_east_face() {
+        let map = room();
+        let hit = cast_ray_dir(&map, (200.0, 80.0), (-1.0, 0.0)).unwrap();
+        assert_eq!(hit.distance, 136.0);
+        assert_eq!(hit.cell, (0, 1));
+        assert_eq!((hit.side, hit.face), (Side::Vertical, Face::East));
+        assert_eq!(hit.tex_u, 0.25);
+    }
+
+    #[test]
+    fn straight_up_and_down_hit_south_and_north_faces() {
+        let map = room();
+        //up into the pillar from below it
+        let hit = cast_ray_dir(&map, (176.0, 224.0), (0.0, -1.0)).unwrap();
+        assert_eq!(hit.distance, 32.0);
+        assert_eq!((hit.tile, hit.cell), (2, (2, 2)));
+        assert_eq!((hit.side, hit.face), (Side::Horizontal, Face::South));
+        assert_eq!(hit.tex_u, 0.75);
+
+        //down to the bottom wall
+        let hit = cast_ray_dir(&map, (96.0, 100.0), (0.0, 1.0)).unwrap();
+        assert_eq!(hit.distance, 156.0);
+        assert_eq!(hit.cell, (1, 4));
+        assert_eq!((hit.side, hit.face), (Side::Horizontal, Face::North));
+        assert_eq!(hit.tex_u, 0.5);
+    }
+
+    #[test]
+    fn diagonal_ray_hits_the_pillar_top() {
+        //crosses x = 128 inside tile (2, 1), then y = 128 into the pillar
+        let map = room();
+        let hit = cast_ray_dir(&map, (100.0, 90.0), (1.0, 1.0)).unwrap();
+        assert_eq!(hit.distance, 38.0);
+        assert_eq!(hit.hit_point, (138.0, 128.0));
+        assert_eq!(hit.cell, (2, 2));
+        assert_eq!((hit.side, hit.face), (Side::Horizontal, Face::North));
+        assert_eq!(hit.tex_u, 10.0 / 64.0);
+    }
+
+    #[test]
+    fn distance_is_in_lengths_of_the_direction() {
+        let map = room();
+        let hit = cast_ray_dir(&map, (96.0, 96.0), (2.0, 0.0)).unwrap();
+        assert_eq!(hit.distance, 80.0);
+        assert_eq!(hit.hit_point, (256.0, 96.0));
+    }
+
+    #[test]
+    fn angles_point_the_same_way_as_directions() {
+        let map = room();
+        //0 degrees is east
+        let hit = cast_ray(&map, (96.0, 96.0), 0.0).unwrap();
+        assert_eq!((hit.distance, hit.face), (160.0, Face::West));
+        //90 degrees is up, cos(90) is not quite 0 so only nearly exact
+        let hit = cast_ray(&map, (96.0, 160.0), 90.0).unwrap();
+        assert_eq!((hit.cell, hit.face), ((1, 0), Face::South));
+        assert!((hit.distance - 96.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn misses_when_leaving_the_map_or_not_moving() {
+        let map = Map::new(3, 1, 64.0, vec![0, 0, 0]);
+        assert_eq!(cast_ray_dir(&map, (32.0, 32.0), (1.0, 0.0)), None);
+        assert_eq!(cast_ray_dir(&room(), (96.0, 96.0), (0.0, 0.0)), None);
+    }
+
+    #[test]
+    fn sees_across_open_floor() {
+        let map = room();
+        assert!(has_line_of_sight(&map, (96.0, 96.0), (224.0, 96.0)));
+        assert!(has_line_of_sight(&map, (96.0, 96.0), (96.0, 224.0)));
+        assert!(has_line_of_sight(&map, (96.0, 96.0), (96.0, 96.0)));
+    }
+
+    #[test]
+    fn pillar_blocks_both_ways() {
+        let map = room();
+        let (a, b) = ((96.0, 160.0), (224.0, 160.0));
+        assert!(!has_line_of_sight(&map, a, b));
+        assert!(!has_line_of_sight(&map, b, a));
+        let (a, b) = ((80.0, 80.0), (230.0, 230.0));
+        assert!(!has_line_of_sight(&map, a, b));
+        assert!(!has_line_of_sight(&map, b, a));
+    }
+
+    #[test]
+    fn stops_at_the_target_not_the_wall_behind_it() {
+        //the pillar is past b, so it must not count
+        let map = room();
+        assert!(has_line_of_sight(&map, (96.0, 160.0), (120.0, 160.0)));
+    }
+
+    #[test]
+    fn can_not_see_into_walls_or_off_the_map() {
+        let map = room();
+        assert!(!has_line_of_sight(&map, (96.0, 96.0), (160.0, 160.0)));
+        assert!(!has_line_of_sight(&map, (96.0, 96.0), (96.0, -50.0)));
+    }
+}
@@ -0,0 +1,449 @@
+//RENDERING
+//draws the world with piston_window: top down map on the left, 3D view on the right
+
+use crate::map::Map;
+use crate::shading::{scale_light, Shading};
+use crate::soft::{self, Bitmap};
+use crate::textures::{Region, TextureRegistry};
+use crate::view::{self, Camera, SpriteRect, SpriteSlice, WallStrip, BACKGROUND_COLOR};
+use crate::world::{Decoration, Enemy, Pickup, Player, Projectile, World};
+use piston_window::texture::{CreateTexture, Format, UpdateTexture};
+use piston_window::*;
+
+//smallest window, it grows to fit bigger 3D views
+pub const SCREEN_W: f64 = 1024.0;
+pub const SCREEN_H: f64 = 512.0;
+
+//MAP BUILDING
+const MINIMAP_SIZE: f64 = 512.0; //2D top view is scaled to fit in this many pixels
+
+//3D view starts at x = 530
+const VIEW_X: f64 = 530.0;
+
+//window size that fits the map and the camera's 3D view side by side
+pub fn window_size(camera: &Camera) -> [u32; 2] {
+    let w = SCREEN_W.max(VIEW_X + camera.width);
+    let h = SCREEN_H.max(camera.height);
+    [w.ceil() as u32, h.ceil() as u32]
+}
+
+//COLOR DEFINITIONS
+const EMPTY_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+const WALL_COLOR: [f32; 4] = [0.6, 0.6, 0.6, 1.0]; //minimap walls other than ids 1 and 2
+const PLAYER_COLOR: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
+const RAY_COLOR: [f32; 4] = [0.0, 0.8, 0.0, 1.0];
+
+//draws a whole frame: map, player, rays, walls and every sprite
+#[allow(clippy::too_many_arguments)] //everything a frame needs, each already its own struct
+pub fn draw_world(
+    world: &World,
+    camera: &Camera,
+    shading: &Shading,
+    transform: math::Matrix2d,
+    g: &mut G2d,
+    textures: &TextureRegistry,
+    atlases: &Atlases,
+    floor: &FloorLayer,
+) {
+    clear(BACKGROUND_COLOR, g);
+
+    //scale the top view so maps of any size fit on the left of the screen
+    let map = &world.map;
+    let map_scale = MINIMAP_SIZE / map.world_width().max(map.world_height());
+    let map_transform = transform.scale(map_scale, map_scale);
+
+    let strips = view::cast_walls(map, &world.player, camera);
+    let depth = view::depth_buffer(&strips);
+
+    draw_map(map, map_transform, g);
+    draw_player(&world.player, map_transform, g);
+    draw_rays(&world.player, &strips, map_transform, g);
+
+    for enemy in &world.enemies {
+        draw_enemy_2d(enemy, map_transform, g);
+    }
+
+    for projectile in world.projectiles.active() {
+        draw_projectile_2d(projectile, map_transform, g);
+    }
+
+    for pickup in &world.pickups {
+        draw_pickup_2d(pickup, map_transform, g);
+    }
+    for decoration in &world.decorations {
+        draw_decoration_2d(decoration, map_transform, g);
+    }
+
+    //3D view
+    let view_transform = transform.trans(VIEW_X, 0.0);
+    image(&floor.texture, view_transform, g);
+    draw_walls(
+        world,
+        &strips,
+        shading,
+        view_transform,
+        g,
+        textures,
+        atlases,
+    );
+
+    //sprites go far to near so nearer ones paint over, and are cut into one slice per ray
+    //column so walls hide exactly the columns they cover
+    for (sprite, rect) in view::sorted_sprites(world, camera) {
+        let region = match textures.sprite_frame(sprite.name, sprite.animation, sprite.view_angle) {
+            Some(region) => region,
+            None => continue,
+        };
+        let slices = view::sprite_slices(&rect, &depth, camera);
+        let look = SpriteLook {
+            region,
+            light: world.light_at(sprite.x, sprite.y),
+            fog: shading.fog_amount(rect.depth, map.tile_size) as f32,
+            fog_color: shading.fog_color,
+        };
+        draw_sprite(&rect, &slices, &look, atlases, view_transform, g);
+    }
+
+    if let Some(tint) = view::damage_tint(world) {
+        rectangle(
+            tint,
+            [0.0, 0.0, camera.width, camera.height],
+            view_transform,
+            g,
+        );
+    }
+    draw_status(&world.player, camera, view_transform, g);
+}
+
+//top down view of the tile grid
+pub fn draw_map(map: &Map, transform: math::Matrix2d, g: &mut G2d) {
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let xo = x as f64 * map.tile_size;
+            let yo = y as f64 * map.tile_size;
+
+            let tile = map.tiles[y * map.width + x];
+
+            // set color based on number in matrix
+            let color = match tile {
+                0 => EMPTY_COLOR,
+                1 => [1.0, 0.0, 0.0, 1.0], // red
+                2 => [0.0, 0.0, 1.0, 1.0], // blue
+                _ => WALL_COLOR,           // any other id from the texture manifest
+            };
+            rectangle(color, [xo, yo, map.tile_size, map.tile_size], transform, g);
+        }
+    }
+}
+
+//PLAYER
+
+pub fn draw_player(player: &Player, transform: math::Matrix2d, g: &mut G2d) {
+    let rect = [player.px - 4.0, player.py - 4.0, 8.0, 8.0];
+    rectangle(PLAYER_COLOR, rect, transform, g); //draws player as yellow rectangle centered on px, py
+
+    let x2 = player.px + player.pdx * 20.0; // calculates where to have line extend to
+    let y2 = player.py + player.pdy * 20.0; // calculates where to have line extend to
+    line(
+        PLAYER_COLOR,
+        2.0f64,
+        [player.px, player.py, x2, y2],
+        transform,
+        g,
+    ); // extends line 20px from player position and direction vector
+}
+
+//Draws 2D line from player to every wall a ray hit
+pub fn draw_rays(
+    player: &Player,
+    strips: &[Option<WallStrip>],
+    transform: math::Matrix2d,
+    g: &mut G2d,
+) {
+    for strip in strips.iter().flatten() {
+        let (rx, ry) = strip.hit_point;
+        line(
+            RAY_COLOR,
+            2.0f64,
+            [player.px, player.py, rx, ry],
+            transform,
+            g,
+        );
+    }
+}
+
+//3D VIEW
+
+//one textured column per ray, transform should put (0, 0) at the top left of the 3D view
+pub fn draw_walls(
+    world: &World,
+    strips: &[Option<WallStrip>],
+    shading: &Shading,
+    transform: math::Matrix2d,
+    g: &mut G2d,
+    textures: &TextureRegistry,
+    atlases: &Atlases,
+) {
+    for strip in strips.iter().flatten() {
+        let region = textures.region_or_fallback(strip.tile);
+        let size = region.size as f64;
+
+        //where along the tile face the ray hit, scaled to texture pixels inside the atlas cell
+        let hit_offset = (strip.tex_u * size).floor().min(size - 1.0);
+        let src = [region.x as f64 + hit_offset, region.y as f64, 1.0, size];
+
+        //light and side shading tint the texture, then fog is laid over it with the fog amount as alpha
+        let [r, gr, b] = scale_light(
+            world.wall_light(strip.cell, strip.face),
+            shading.side_shade(strip.side),
+        );
+        Image::new_color([r, gr, b, 1.0]).src_rect(src).draw(
+            &atlases.textures[region.atlas],
+            &DrawState::default(),
+            transform
+                .trans(strip.x, strip.top)
+                .scale(strip.width, strip.height / size),
+            g,
+        );
+
+        let fog = shading.fog_amount(strip.depth, world.map.tile_size) as f32;
+        if fog > 0.0 {
+            let mut color = shading.fog_color;
+            color[3] = fog;
+            let rect = [strip.x, strip.top, strip.width, strip.height];
+            rectangle(color, rect, transform, g);
+        }
+    }
+}
+
+//ATLASES
+//the registry's atlas images uploaded to the GPU, in the same order as TextureRegistry::atlases
+pub struct Atlases {
+    pub textures: Vec<G2dTexture>,
+    //same images in plain white with the alpha kept, tinted with the fog color to fog sprites
+    pub silhouettes: Vec<G2dTexture>,
+}
+
+impl Atlases {
+    pub fn new(window: &mut PistonWindow, registry: &TextureRegistry) -> Self {
+        let mut context = window.create_texture_context();
+        let mut upload = |bitmap: &Bitmap| {
+            Texture::create(
+                &mut context,
+                Format::Rgba8,
+                &bitmap.pixels,
+                [bitmap.width as u32, bitmap.height as u32],
+                &TextureSettings::new(),
+            )
+            .unwrap()
+        };
+        let textures = registry
+            .atlases
+            .iter()
+            .map(|atlas| upload(&atlas.bitmap))
+            .collect();
+        let silhouettes = registry
+            .atlases
+            .iter()
+            .map(|atlas| upload(&silhouette(&atlas.bitmap)))
+            .collect();
+        Self {
+            textures,
+            silhouettes,
+        }
+    }
+}
+
+//white copy of an image that keeps its alpha
+fn silhouette(bitmap: &Bitmap) -> Bitmap {
+    let mut pixels = bitmap.pixels.clone();
+    for px in pixels.chunks_exact_mut(4) {
+        px[0] = 255;
+        px[1] = 255;
+        px[2] = 255;
+    }
+    Bitmap::from_rgba(bitmap.width, bitmap.height, pixels)
+}
+
+//FLOOR AND CEILING
+//piston has no per pixel drawing, so floors and ceilings are cast into a bitmap by the
+//software renderer and uploaded as a texture the size of the 3D view every frame
+pub struct FloorLayer {
+    pub bitmap: Bitmap,
+    pub texture: G2dTexture,
+    pub context: G2dTextureContext,
+}
+
+impl FloorLayer {
+    pub fn new(window: &mut PistonWindow, camera: &Camera) -> Self {
+        let bitmap = Bitmap::for_view(camera);
+        let mut context = window.create_texture_context();
+        let texture = Texture::create(
+            &mut context,
+            Format::Rgba8,
+            &bitmap.pixels,
+            [bitmap.width as u32, bitmap.height as u32],
+            &TextureSettings::new(),
+        )
+        .unwrap();
+        Self {
+            bitmap,
+            texture,
+            context,
+        }
+    }
+
+    //recasts the floor and ceiling for this frame, the encoder still has to be flushed before drawing
+    pub fn update(
+        &mut self,
+        world: &World,
+        camera: &Camera,
+        shading: &Shading,
+        textures: &TextureRegistry,
+    ) {
+        self.bitmap.clear([0, 0, 0, 0]); //transparent where there is no floor or ceiling
+        soft::draw_floor_and_ceiling(world, camera, shading, &mut self.bitmap, textures);
+        //called through the trait, Texture has its own update taking an image
+        UpdateTexture::update(
+            &mut self.texture,
+            &mut self.context,
+            Format::Rgba8,
+            &self.bitmap.pixels,
+            [0, 0],
+            [self.bitmap.width as u32, self.bitmap.height as u32],
+        )
+        .unwrap();
+    }
+}
+
+//SPRITES
+
+//which texture a sprite is drawn with and how it is lit
+pub struct SpriteLook {
+    pub region: Region,
+    pub light: [f32; 3],     //light at the sprite's feet
+    pub fog: f32,            //fog amount for the sprite's distance
+    pub fog_color: [f32; 4], //shading's fog color
+}
+
+//draws the visible slices of a sprite, each one showing its part of the texture
+//the texture is tinted by the light, then its silhouette is laid over it in the fog color
+//so see through texels stay see through
+pub fn draw_sprite(
+    rect: &SpriteRect,
+    slices: &[SpriteSlice],
+    look: &SpriteLook,
+    atlases: &Atlases,
+    transform: math::Matrix2d,
+    g: &mut G2d,
+) {
+    let region = look.region;
+    let size = region.size as f64;
+    let [r, gr, b] = look.light;
+    let mut fog_color = look.fog_color;
+    fog_color[3] = look.fog;
+
+    for slice in slices {
+        let src = [
+            region.x as f64 + slice.u0 * size,
+            region.y as f64,
+            (slice.u1 - slice.u0) * size,
+            size,
+        ];
+        let dst = [slice.x, rect.y, slice.w, rect.h];
+        Image::new_color([r, gr, b, 1.0])
+            .src_rect(src)
+            .rect(dst)
+            .draw(
+                &atlases.textures[region.atlas],
+                &DrawState::default(),
+                transform,
+                g,
+            );
+        if look.fog > 0.0 {
+            Image::new_color(fog_color).src_rect(src).rect(dst).draw(
+                &atlases.silhouettes[region.atlas],
+                &DrawState::default(),
+                transform,
+                g,
+            );
+        }
+    }
+}
+
+//STATUS BARS
+//hit points (red) and armor (blue) along the bottom left of the 3D view
+const BAR_W: f64 = 150.0;
+const BAR_H: f64 = 10.0;
+
+pub fn draw_status(player: &Player, camera: &Camera, transform: math::Matrix2d, g: &mut G2d) {
+    let health = &player.health;
+    let bars = [
+        ([0.8, 0.1, 0.1, 1.0], health.fraction()),
+        (
+            [0.2, 0.4, 1.0, 1.0],
+            (health.armor / health.max_hp).min(1.0),
+        ),
+    ];
+    for (i, (color, fill)) in bars.into_iter().enumerate() {
+        let y = camera.height - (i + 1) as f64 * (BAR_H + 4.0) - 4.0;
+        rectangle([0.0, 0.0, 0.0, 0.6], [8.0, y, BAR_W, BAR_H], transform, g);
+        rectangle(color, [8.0, y, BAR_W * fill, BAR_H], transform, g);
+    }
+}
+
+//ENEMY
+
+//top view
+pub fn draw_enemy_2d(enemy: &Enemy, transform: math::Matrix2d, g: &mut G2d) {
+    if !enemy.alive {
+        return;
+    }
+
+    ellipse(
+        [0.6, 0.0, 0.8, 1.0],
+        [enemy.x - 8.0, enemy.y - 8.0, 16.0, 16.0],
+        transform,
+        g,
+    );
+}
+
+//PROJECTILES
+
+//projectile on top down view
+pub fn draw_projectile_2d(projectile: &Projectile, transform: math::Matrix2d, g: &mut G2d) {
+    if !projectile.active {
+        return;
+    }
+    ellipse(
+        [1.0, 1.0, 1.0, 1.0],
+        [projectile.x - 2.0, projectile.y - 2.0, 4.0, 4.0],
+        transform,
+        g,
+    );
+}
+
+//PICKUPS AND DECORATIONS
+
+//pickups on top down view, gone once taken
+pub fn draw_pickup_2d(pickup: &Pickup, transform: math::Matrix2d, g: &mut G2d) {
+    if pickup.taken {
+        return;
+    }
+    ellipse(
+        [0.2, 0.9, 0.3, 1.0],
+        [pickup.x - 4.0, pickup.y - 4.0, 8.0, 8.0],
+        transform,
+        g,
+    );
+}
+
+//decorations on top down view
+pub fn draw_decoration_2d(decoration: &Decoration, transform: math::Matrix2d, g: &mut G2d) {
+    rectangle(
+        [0.6, 0.6, 0.6, 1.0],
+        [decoration.x - 4.0, decoration.y - 4.0, 8.0, 8.0],
+        transform,
+        g,
+    );
+}
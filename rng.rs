@@ -0,0 +1,28 @@
+//RANDOM NUMBERS
+//small xorshift generator for gameplay rolls such as whether a shot hits
+//seeded, so the same inputs play out the same way every run (handy for tests and replays)
+
+pub struct Rng {
+    state: u64, //never 0, xorshift would get stuck there
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed.max(1) }
+    }
+
+    //next number from 0.0 up to but not including 1.0
+    pub fn next_f64(&mut self) -> f64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        (x >> 11) as f64 / (1u64 << 53) as f64 //top 53 bits fill an f64's fraction exactly
+    }
+
+    //next number from -1.0 up to 1.0
+    pub fn next_signed(&mut self) -> f64 {
+        self.next_f64() * 2.0 - 1.0
+    }
+}
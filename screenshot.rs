@@ -0,0 +1,123 @@
+//SCREENSHOTS
+//decides when and where frames from the software renderer get written as PNG files
+//used for visual bug reports (one shot on a key press) and regression baselines (every Nth frame)
+//
+//files already in the folder are never overwritten: screenshots carry on numbering after the
+//highest shot there, and each run's frame dumps get their own run number
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_DIR: &str = "screenshots";
+
+pub struct Recorder {
+    pub dir: PathBuf,       //folder the PNGs are written to
+    pub every: Option<u64>, //dump every Nth frame, None to only take screenshots on request
+    frame: u64,             //frames drawn so far
+    shots: u64,             //number of the last screenshot, counting ones from earlier runs
+    run: u64,               //number of this run, kept in the frame dump names
+}
+
+impl Recorder {
+    pub fn new<P: Into<PathBuf>>(dir: P, every: Option<u64>) -> Self {
+        let dir = dir.into();
+        let shots = highest_number(&dir, "shot_");
+        let run = highest_number(&dir, "frame_") + 1;
+        Self {
+            dir,
+            every: every.filter(|&n| n > 0), //every 0th frame means never
+            frame: 0,
+            shots,
+            run,
+        }
+    }
+
+    //call once per drawn frame, gives the file to dump this frame to if it is one of the Nth frames
+    pub fn next_frame(&mut self) -> Option<PathBuf> {
+        let frame = self.frame;
+        self.frame += 1;
+        match self.every {
+            Some(n) if frame.is_multiple_of(n) => Some(
+                self.dir
+                    .join(format!("frame_{:03}_{:06}.png", self.run, frame)),
+            ),
+            _ => None,
+        }
+    }
+
+    //file for the next screenshot taken on request
+    pub fn next_screenshot(&mut self) -> PathBuf {
+        self.shots += 1;
+        self.dir.join(format!("shot_{:04}.png", self.shots))
+    }
+}
+
+//highest number right after prefix in the names of the PNGs in dir, 0 if there are none
+//(or the folder does not exist yet)
+fn highest_number(dir: &Path, prefix: &str) -> u64 {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            if !name.ends_with(".png") {
+                return None;
+            }
+            let rest = name.strip_prefix(prefix)?;
+            let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+            digits.parse().ok()
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //empty folder of its own for one test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("raycaster_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn numbering_starts_at_one_in_a_new_folder() {
+        let dir = std::env::temp_dir().join("raycaster_does_not_exist");
+        let mut recorder = Recorder::new(&dir, Some(2));
+        assert_eq!(recorder.next_screenshot(), dir.join("shot_0001.png"));
+        assert_eq!(
+            recorder.next_frame(),
+            Some(dir.join("frame_001_000000.png"))
+        );
+        assert_eq!(recorder.next_frame(), None);
+        assert_eq!(
+            recorder.next_frame(),
+            Some(dir.join("frame_001_000002.png"))
+        );
+    }
+
+    #[test]
+    fn carries_on_after_files_from_earlier_runs() {
+        let dir = temp_dir("carries_on");
+        for name in [
+            "shot_0001.png",
+            "shot_0007.png",
+            "frame_002_000010.png",
+            "notes.txt",
+        ] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        let mut recorder = Recorder::new(&dir, Some(1));
+        assert_eq!(recorder.next_screenshot(), dir.join("shot_0008.png"));
+        assert_eq!(
+            recorder.next_frame(),
+            Some(dir.join("frame_003_000000.png"))
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+//SHADING
+//darkens walls by which side was hit and fades everything into fog with distance
+//both renderers use this so the window and screenshots look the same
+
+use crate::lighting::WHITE_LIGHT;
+use crate::raycast::Side;
+use crate::view::BACKGROUND_COLOR;
+
+//north/south faces are drawn darker than east/west ones so corners stand out
+pub const DEFAULT_HORIZONTAL_SHADE: f64 = 0.7;
+//how quickly the fog closes in, per tile of distance (0 turns fog off)
+pub const DEFAULT_FOG_DENSITY: f64 = 0.08;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shading {
+    pub vertical_shade: f64, //brightness of east and west wall faces, 0.0 to 1.0
+    pub horizontal_shade: f64, //brightness of north and south wall faces, 0.0 to 1.0
+    pub fog_color: [f32; 4], //what far away things fade into
+    pub fog_density: f64,    //fog per tile of distance, fog = 1 - e^(-density * tiles)
+}
+
+impl Shading {
+    //full brightness and no fog, everything is drawn as it is in the texture
+    pub fn none() -> Self {
+        Self {
+            vertical_shade: 1.0,
+            horizontal_shade: 1.0,
+            fog_color: BACKGROUND_COLOR,
+            fog_density: 0.0,
+        }
+    }
+
+    //brightness of a wall face
+    pub fn side_shade(&self, side: Side) -> f64 {
+        match side {
+            Side::Vertical => self.vertical_shade,
+            Side::Horizontal => self.horizontal_shade,
+        }
+    }
+
+    //how much of the fog color covers something this far away, 0.0 (none) to 1.0 (all fog)
+    pub fn fog_amount(&self, distance: f64, tile_size: f64) -> f64 {
+        if self.fog_density <= 0.0 {
+            return 0.0;
+        }
+        1.0 - (-self.fog_density * distance / tile_size).exp()
+    }
+
+    //multiplies a color by the light falling on it, then mixes in fog for its distance
+    //light is usually the light map color times side_shade, alpha is kept
+    pub fn apply(
+        &self,
+        color: [f32; 4],
+        light: [f32; 3],
+        distance: f64,
+        tile_size: f64,
+    ) -> [f32; 4] {
+        let fog = self.fog_amount(distance, tile_size) as f32;
+        let mix = |c: f32, l: f32, f: f32| c * l * (1.0 - fog) + f * fog;
+        [
+            mix(color[0], light[0], self.fog_color[0]),
+            mix(color[1], light[1], self.fog_color[1]),
+            mix(color[2], light[2], self.fog_color[2]),
+            color[3],
+        ]
+    }
+
+    //same as apply for 8 bit texels
+    pub fn apply_rgba8(
+        &self,
+        color: [u8; 4],
+        light: [f32; 3],
+        distance: f64,
+        tile_size: f64,
+    ) -> [u8; 4] {
+        if light == WHITE_LIGHT && self.fog_density <= 0.0 {
+            return color; //common case when shading is off, skip the float round trip
+        }
+        let f = |c: u8| c as f32 / 255.0;
+        let out = self.apply(
+            [f(color[0]), f(color[1]), f(color[2]), f(color[3])],
+            light,
+            distance,
+            tile_size,
+        );
+        let c = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        [c(out[0]), c(out[1]), c(out[2]), color[3]]
+    }
+}
+
+//light color scaled by a brightness such as a side shade
+pub fn scale_light(light: [f32; 3], brightness: f64) -> [f32; 3] {
+    let b = brightness as f32;
+    [light[0] * b, light[1] * b, light[2] * b]
+}
+
+impl Default for Shading {
+    fn default() -> Self {
+        Self {
+            vertical_shade: 1.0,
+            horizontal_shade: DEFAULT_HORIZONTAL_SHADE,
+            fog_color: BACKGROUND_COLOR,
+            fog_density: DEFAULT_FOG_DENSITY,
+        }
+    }
+}
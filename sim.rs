@@ -0,0 +1,544 @@
+//SIMULATION
+//moves everything in the world forward one tick, nothing here draws
+
+use crate::ai::{self, Attack, Ranged};
+use crate::damage::{Damage, DamageKind, Event, Hurt};
+use crate::lighting::Flash;
+use crate::map::Map;
+use crate::raycast::{cast_ray_dir, has_line_of_sight};
+use crate::world::{deg_to_rad, fix_angle, Owner, Player, Projectile, World, PAIN_FLASH};
+
+//PLAYER SHOTS
+const BULLET_DAMAGE: Damage = Damage {
+    amount: 25.0,
+    kind: DamageKind::Bullet,
+};
+const BULLET_SPEED: f64 = 600.0; //pixels per second
+const BULLET_LIFETIME: f64 = 2.0; //seconds
+const HIT_RADIUS: f64 = 6.0; //enemies within this many pixels of a projectile are hit
+
+//ENEMY ATTACKS
+const ENEMY_MELEE_DAMAGE: Damage = Damage {
+    amount: 15.0,
+    kind: DamageKind::Melee,
+};
+const ENEMY_HITSCAN_DAMAGE: Damage = Damage {
+    amount: 8.0,
+    kind: DamageKind::Bullet,
+};
+const ENEMY_SHOT_DAMAGE: Damage = Damage {
+    amount: 12.0,
+    kind: DamageKind::Fire,
+};
+const ENEMY_SHOT_SPEED: f64 = 300.0; //pixels per second, slow enough to dodge
+const ENEMY_SHOT_LIFETIME: f64 = 3.0; //seconds
+const MAX_AIM_ERROR: f64 = 15.0; //degrees a shot can go wide at the worst accuracy
+
+//health pickups give this many hit points back
+const HEALTH_PICKUP: &str = "health";
+const HEALTH_PICKUP_HP: f64 = 25.0;
+
+// KEY PRESSING STRUCTURE
+pub struct Pressed {
+    pub w: bool,
+    pub a: bool,
+    pub s: bool,
+    pub d: bool,
+}
+impl Pressed {
+    pub fn new() -> Self {
+        Self {
+            w: false,
+            a: false,
+            s: false,
+            d: false,
+        }
+    }
+}
+
+impl Default for Pressed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Player {
+    //uses keys a, d, w, s to move the position of the player
+    // (w and s move forward or backward (according to direction). a and d rotate angle
+    pub fn update(&mut self, pressed: &Pressed, dt: f64, map: &Map) {
+        // rotate
+        if pressed.a {
+            self.pa += 2.0 * 60.0 * dt;
+        }
+        if pressed.d {
+            self.pa -= 2.0 * 60.0 * dt;
+        }
+
+        self.pa = fix_angle(self.pa);
+
+        let rad = deg_to_rad(self.pa);
+        self.pdx = rad.cos();
+        self.pdy = -rad.sin();
+
+        // move 2 px
+        let speed = 2.0 * 60.0;
+
+        //W and S move forward or backward 5 pixels
+        if pressed.w {
+            self.px += self.pdx * speed * dt;
+            self.py += self.pdy * speed * dt;
+            if map.is_wall_at(self.px, self.py) {
+                self.px -= self.pdx * speed * dt;
+                self.py -= self.pdy * speed * dt;
+            }
+            // Print the array index of player location
+            // println!("Player X: {}", (self.px / map.tile_size) as i32);
+            // println!("Player Y: {}", (self.py / map.tile_size) as i32);
+        }
+        if pressed.s {
+            self.px -= self.pdx * speed * dt;
+            self.py -= self.pdy * speed * dt;
+
+            if map.is_wall_at(self.px, self.py) {
+                self.px += self.pdx * speed * dt;
+                self.py += self.pdy * speed * dt;
+            }
+        }
+    }
+}
+
+impl Projectile {
+    //shot from the player's current position the way they are facing
+    pub fn player_shot(player: &Player) -> Self {
+        Self {
+            x: player.px,
+            y: player.py,
+            dx: player.pdx,
+            dy: player.pdy,
+            speed: BULLET_SPEED,
+            damage: BULLET_DAMAGE,
+            owner: Owner::Player,
+            time_left: BULLET_LIFETIME,
+            active: true,
+        }
+    }
+
+    //shot from an enemy standing at from, flying at angle degrees
+    pub fn enemy_shot(enemy: usize, from: (f64, f64), angle: f64) -> Self {
+        let rad = deg_to_rad(angle);
+        Self {
+            x: from.0,
+            y: from.1,
+            dx: rad.cos(),
+            dy: -rad.sin(), //negative because y increases down
+            speed: ENEMY_SHOT_SPEED,
+            damage: ENEMY_SHOT_DAMAGE,
+            owner: Owner::Enemy(enemy),
+            time_left: ENEMY_SHOT_LIFETIME,
+            active: true,
+        }
+    }
+
+    //moves the projectile, stopping it at walls, the edge of the map or when its time is up
+    //false once it has stopped
+    fn fly(&mut self, dt: f64, map: &Map) -> bool {
+        self.time_left -= dt;
+        if self.time_left <= 0.0 {
+            self.active = false;
+            return false;
+        }
+
+        let step = self.speed * dt; //how far the projectile moves this frame
+
+        //wall collision: look along the flight path so a fast projectile can't skip over a wall in one frame
+        if let Some(hit) = cast_ray_dir(map, (self.x, self.y), (self.dx, self.dy)) {
+            if hit.distance <= step {
+                self.active = false;
+                return false;
+            }
+        }
+
+        self.x += self.dx * step;
+        self.y += self.dy * step;
+
+        //out of map bounds (if leaves rectange, kill projectile)
+        if self.x < 0.0
+            || self.x >= map.world_width()
+            || self.y < 0.0
+            || self.y >= map.world_height()
+        {
+            self.active = false;
+            return false;
+        }
+        true
+    }
+
+    //radius hit check against something standing at (x, y)
+    fn touches(&self, x: f64, y: f64, radius: f64) -> bool {
+        let dx = self.x - x; //offset from the target to projectile
+        let dy = self.y - y; //offset from the target to projectile
+        dx * dx + dy * dy <= radius * radius
+    }
+}
+
+//what a projectile hit this tick
+enum Target {
+    Player,
+    Enemy(usize),
+}
+
+impl World {
+    //one simulation step, called from the game loop's update event
+    pub fn update(&mut self, pressed: &Pressed, dt: f64) {
+        self.events.clear();
+        if !self.game_over() {
+            self.player.update(pressed, dt, &self.map);
+        }
+        self.player.pain = (self.player.pain - dt).max(0.0);
+
+        self.update_projectiles(dt);
+
+        let mut attacks = Vec::new();
+        for (i, enemy) in self.enemies.iter_mut().enumerate() {
+            if let Some(attack) = enemy.update(&self.player, &self.map, &self.nav, dt) {
+                attacks.push((i, attack));
+            }
+            enemy.animation.update(dt);
+        }
+        for (i, attack) in attacks {
+            self.enemy_attack(i, attack);
+        }
+        self.collect_pickups();
+        self.update_lights(dt);
+    }
+
+    //moves every projectile and damages whatever they hit
+    //the player's shots hit enemies and enemy shots hit the player, nobody is hit by their own
+    fn update_projectiles(&mut self, dt: f64) {
+        let mut hits = Vec::new();
+        for projectile in self.projectiles.active_mut() {
+            if !projectile.fly(dt, &self.map) {
+                continue;
+            }
+            let target = match projectile.owner {
+                //the first enemy close enough stops the shot
+                Owner::Player => self
+                    .enemies
+                    .iter()
+                    .position(|e| e.alive && projectile.touches(e.x, e.y, HIT_RADIUS))
+                    .map(Target::Enemy),
+                //the player is a quarter of a tile wide each way
+                Owner::Enemy(_) => {
+                    let radius = self.map.tile_size / 4.0;
+                    let hit = projectile.touches(self.player.px, self.player.py, radius);
+                    hit.then_some(Target::Player)
+                }
+            };
+            if let Some(target) = target {
+                projectile.active = false;
+                hits.push((target, projectile.damage));
+            }
+        }
+
+        for (target, damage) in hits {
+            match target {
+                Target::Player => self.hurt_player(damage),
+                //only the player's shots hit enemies, so the enemy turns on the player
+                Target::Enemy(i) => {
+                    let from = (self.player.px, self.player.py);
+                    self.hurt_enemy(i, damage, from);
+                }
+            }
+        }
+    }
+
+    //damages one enemy, from is where the attack came from
+    pub fn hurt_enemy(&mut self, index: usize, damage: Damage, from: (f64, f64)) {
+        let enemy = match self.enemies.get_mut(index) {
+            Some(enemy) => enemy,
+            None => return,
+        };
+        match enemy.hurt(damage, from) {
+            Hurt::Ignored => {}
+            Hurt::Pain(_) => self.events.push(Event::EnemyHurt {
+                enemy: index,
+                damage,
+            }),
+            Hurt::Killed => self.events.push(Event::EnemyKilled {
+                enemy: index,
+                damage,
+            }),
+        }
+    }
+
+    //damages the player, the last hit point lost ends the game
+    pub fn hurt_player(&mut self, damage: Damage) {
+        match self.player.health.take(damage) {
+            Hurt::Ignored => {}
+            Hurt::Pain(_) => {
+                self.player.pain = PAIN_FLASH;
+                self.events.push(Event::PlayerHurt { damage });
+            }
+            Hurt::Killed => {
+                self.player.pain = PAIN_FLASH;
+                self.events.push(Event::PlayerKilled { damage });
+            }
+        }
+    }
+
+    //carries out an attack an enemy decided on, a dead player is left alone
+    //shots are less likely to land the further away the player is
+    fn enemy_attack(&mut self, index: usize, attack: Attack) {
+        if self.game_over() {
+            return;
+        }
+        let enemy = &self.enemies[index];
+        let from = (enemy.x, enemy.y);
+        let to = (self.player.px, self.player.py);
+        if !has_line_of_sight(&self.map, from, to) {
+            return; //the player ducked out of sight this tick
+        }
+        let distance = ((to.0 - from.0).powi(2) + (to.1 - from.1).powi(2)).sqrt();
+        let accuracy = ai::accuracy(distance / self.map.tile_size);
+
+        match attack {
+            Attack::Melee => self.hurt_player(ENEMY_MELEE_DAMAGE),
+            Attack::Ranged(Ranged::Hitscan) => {
+                if self.rng.next_f64() < accuracy {
+                    self.hurt_player(ENEMY_HITSCAN_DAMAGE);
+                }
+            }
+            Attack::Ranged(Ranged::Projectile) => {
+                //aimed at the player, going wider the worse the accuracy
+                let error = self.rng.next_signed() * (1.0 - accuracy) * MAX_AIM_ERROR;
+                let aim = enemy.angle_to(to.0, to.1) + error;
+                self.projectiles
+                    .spawn(Projectile::enemy_shot(index, from, aim));
+            }
+        }
+    }
+
+    //player picks up anything within half a tile of them
+    //health is left lying there while the player does not need it
+    fn collect_pickups(&mut self) {
+        if self.game_over() {
+            return;
+        }
+        let reach = self.map.tile_size / 2.0;
+        for pickup in &mut self.pickups {
+            if pickup.taken {
+                continue;
+            }
+            let dx = pickup.x - self.player.px;
+            let dy = pickup.y - self.player.py;
+            if dx * dx + dy * dy > reach * reach {
+                continue;
+            }
+            pickup.taken = if pickup.sprite == HEALTH_PICKUP {
+                self.player.health.heal(HEALTH_PICKUP_HP)
+            } else {
+                true
+            };
+        }
+    }
+
+    //player fires, lighting up the area around them if the shot went off
+    //a shot only fails to go off if the projectile pool is full
+    pub fn shoot(&mut self) {
+        if self.game_over() {
+            return;
+        }
+        if self
+            .projectiles
+            .spawn(Projectile::player_shot(&self.player))
+        {
+            let flash = Flash::muzzle(self.player.px, self.player.py, self.map.tile_size);
+            self.flashes.push(flash);
+            self.relight();
+        }
+    }
+
+    //fades flashes out and rebuilds the light map while any are alive
+    fn update_lights(&mut self, dt: f64) {
+        if self.flashes.is_empty() {
+            return;
+        }
+        for flash in &mut self.flashes {
+            flash.time_left -= dt;
+        }
+        self.flashes.retain(|f| f.time_left > 0.0);
+        self.relight();
+    }
+
+    //baked level lights plus whatever flashes are alive right now
+    pub fn relight(&mut self) {
+        self.light_map.clone_from(&self.base_light);
+        for flash in &self.flashes {
+            self.light_map
+                .add_light(&self.map, &flash.light, flash.strength());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::damage::Health;
+    use crate::level::Level;
+    use crate::world::PLAYER_HP;
+
+    //world from a level grid of '#' walls, '.' floor, the player and one enemy
+    fn world(rows: &str) -> World {
+        let text = format!("tile # 1\ntile . 0\nmap\n{}\n", rows);
+        World::from_level(Level::parse(&text).unwrap())
+    }
+
+    #[test]
+    fn melee_hurts_the_player() {
+        let mut world = world("#####\n#PE.#\n#####");
+        world.enemy_attack(0, Attack::Melee);
+        assert_eq!(
+            world.player.health.hp,
+            PLAYER_HP - ENEMY_MELEE_DAMAGE.amount
+        );
+        assert_eq!(
+            world.events,
+            vec![Event::PlayerHurt {
+                damage: ENEMY_MELEE_DAMAGE
+            }]
+        );
+        assert_eq!(world.player.pain, PAIN_FLASH);
+    }
+
+    #[test]
+    fn no_attack_through_a_wall() {
+        let mut world = world("#####\n#P#E#\n#####");
+        world.enemy_attack(0, Attack::Melee);
+        world.enemy_attack(0, Attack::Ranged(Ranged::Hitscan));
+        world.enemy_attack(0, Attack::Ranged(Ranged::Projectile));
+        assert_eq!(world.player.health.hp, PLAYER_HP);
+        assert_eq!(world.projectiles.active().count(), 0);
+        assert!(world.events.is_empty());
+    }
+
+    #[test]
+    fn no_attack_once_the_player_is_dead() {
+        let mut world = world("#####\n#PE.#\n#####");
+        world.player.health.hp = 0.0;
+        world.enemy_attack(0, Attack::Melee);
+        assert!(world.events.is_empty());
+    }
+
+    //how many of 200 hitscan shots hit, with the player healed between shots
+    fn hitscan_hits(world: &mut World) -> usize {
+        (0..200)
+            .filter(|_| {
+                world.player.health = Health::new(PLAYER_HP);
+                world.enemy_attack(0, Attack::Ranged(Ranged::Hitscan));
+                world.player.health.hp < PLAYER_HP
+            })
+            .count()
+    }
+
+    #[test]
+    fn hitscan_misses_more_from_further_away() {
+        //one tile away hits 5 in 6 times, seven tiles away only the minimum 1 in 5
+        let mut near = world("#########\n#PE.....#\n#########");
+        let mut far = world("##########\n#P......E#\n##########");
+        let near_hits = hitscan_hits(&mut near);
+        let far_hits = hitscan_hits(&mut far);
+        assert!((150..=185).contains(&near_hits), "{} near hits", near_hits);
+        assert!((20..=60).contains(&far_hits), "{} far hits", far_hits);
+
+        //the same seed rolls the same shots
+        let mut again = world("##########\n#P......E#\n##########");
+        assert_eq!(hitscan_hits(&mut again), far_hits);
+    }
+
+    #[test]
+    fn shots_go_wider_from_further_away() {
+        //the widest a shot went off the straight line to the player, in degrees
+        fn widest_miss(rows: &str) -> f64 {
+            let mut world = world(rows);
+            let mut widest: f64 = 0.0;
+            for _ in 0..50 {
+                world.enemy_attack(0, Attack::Ranged(Ranged::Projectile));
+                let shot = *world.projectiles.active().next().unwrap();
+                assert_eq!(shot.owner, Owner::Enemy(0));
+                widest = widest.max(shot.dy.atan2(-shot.dx).to_degrees().abs());
+                world
+                    .projectiles
+                    .active_mut()
+                    .for_each(|p| p.active = false);
+            }
+            widest
+        }
+        let near = widest_miss("#########\n#PE.....#\n#########");
+        let far = widest_miss("##########\n#P......E#\n##########");
+        assert!(near <= MAX_AIM_ERROR * (1.0 - ai::accuracy(1.0)) + 1e-9);
+        assert!(far <= MAX_AIM_ERROR * (1.0 - ai::accuracy(7.0)) + 1e-9);
+        assert!(far > near);
+    }
+
+    //a projectile hanging still right on top of at
+    fn still_shot(world: &World, owner: Owner, at: (f64, f64)) -> Projectile {
+        let mut shot = match owner {
+            Owner::Player => Projectile::player_shot(&world.player),
+            Owner::Enemy(i) => Projectile::enemy_shot(i, at, 0.0),
+        };
+        shot.x = at.0;
+        shot.y = at.1;
+        shot.speed = 0.0;
+        shot
+    }
+
+    #[test]
+    fn projectiles_fizzle_out_when_their_time_is_up() {
+        let mut world = world("#####\n#P.E#\n#####");
+        let mut shot = still_shot(&world, Owner::Player, (160.0, 96.0));
+        shot.time_left = 0.25;
+        world.projectiles.spawn(shot);
+        world.update_projectiles(0.1);
+        world.update_projectiles(0.1);
+        assert_eq!(world.projectiles.active().count(), 1);
+        world.update_projectiles(0.1);
+        assert_eq!(world.projectiles.active().count(), 0);
+    }
+
+    #[test]
+    fn enemy_shots_never_hit_enemies() {
+        let mut world = world("######\n#P.EE#\n######");
+        let (x, y) = (world.enemies[1].x, world.enemies[1].y);
+        let shot = still_shot(&world, Owner::Enemy(0), (x, y));
+        world.projectiles.spawn(shot);
+        world.update_projectiles(0.1);
+        assert!(world.enemies.iter().all(|e| e.health.hp == e.health.max_hp));
+        assert_eq!(world.projectiles.active().count(), 1, "flies on through");
+    }
+
+    #[test]
+    fn player_shots_never_hit_the_player() {
+        let mut world = world("#####\n#P.E#\n#####");
+        let at = (world.player.px, world.player.py);
+        let shot = still_shot(&world, Owner::Player, at);
+        world.projectiles.spawn(shot);
+        world.update_projectiles(0.1);
+        assert_eq!(world.player.health.hp, PLAYER_HP);
+        assert_eq!(world.projectiles.active().count(), 1);
+    }
+
+    #[test]
+    fn shots_hit_the_other_side() {
+        let mut world = world("#####\n#P.E#\n#####");
+        let enemy_at = (world.enemies[0].x, world.enemies[0].y);
+        let player_at = (world.player.px, world.player.py);
+        let shot = still_shot(&world, Owner::Player, enemy_at);
+        world.projectiles.spawn(shot);
+        let shot = still_shot(&world, Owner::Enemy(0), player_at);
+        world.projectiles.spawn(shot);
+        world.update_projectiles(0.1);
+        assert_eq!(world.projectiles.active().count(), 0);
+        assert!(world.enemies[0].health.hp < world.enemies[0].health.max_hp);
+        assert!(world.player.health.hp < PLAYER_HP);
+    }
+}
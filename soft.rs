@@ -0,0 +1,291 @@
+//SOFTWARE RENDERER
+//draws the same 3D view as the window into an RGBA buffer in memory
+//needs no GPU or display, so it runs on CI machines and frames can be compared in tests
+
+use crate::projection;
+use crate::shading::{scale_light, Shading};
+use crate::textures::{Region, TextureRegistry};
+use crate::view::{self, Camera, SpriteRect, SpriteSlice, WallStrip, BACKGROUND_COLOR};
+use crate::world::World;
+use std::path::Path;
+
+//converts a piston style 0.0-1.0 color to 8 bit RGBA
+pub fn to_rgba8(color: [f32; 4]) -> [u8; 4] {
+    let c = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [c(color[0]), c(color[1]), c(color[2]), c(color[3])]
+}
+
+//blends src over dst using src's alpha, the same way the window does
+fn blend(dst: [u8; 4], src: [u8; 4]) -> [u8; 4] {
+    if src[3] == 255 {
+        return src;
+    }
+    let a = src[3] as f32 / 255.0;
+    let mix = |d: u8, s: u8| (s as f32 * a + d as f32 * (1.0 - a)).round() as u8;
+    [
+        mix(dst[0], src[0]),
+        mix(dst[1], src[1]),
+        mix(dst[2], src[2]),
+        (src[3] as f32 + dst[3] as f32 * (1.0 - a)).round() as u8,
+    ]
+}
+
+//RGBA IMAGE IN MEMORY
+//used both for textures and for the frame being drawn
+pub struct Bitmap {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>, //4 bytes per pixel, row by row from the top left
+}
+
+//the frame the software renderer draws into
+pub type Framebuffer = Bitmap;
+
+impl Bitmap {
+    //new image filled with transparent black
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height * 4],
+        }
+    }
+
+    pub fn from_rgba(width: usize, height: usize, pixels: Vec<u8>) -> Self {
+        assert_eq!(
+            pixels.len(),
+            width * height * 4,
+            "pixel data does not match image size"
+        );
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    //reads any image file the image crate understands (PNG for now)
+    pub fn load<P: AsRef<Path>>(path: P) -> image::ImageResult<Self> {
+        let img = image::open(path)?.to_rgba8();
+        let (w, h) = img.dimensions();
+        Ok(Self::from_rgba(w as usize, h as usize, img.into_raw()))
+    }
+
+    //writes the image out as a PNG, creating the folder if needed
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> image::ImageResult<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(image::ImageError::IoError)?;
+        }
+        image::save_buffer_with_format(
+            path,
+            &self.pixels,
+            self.width as u32,
+            self.height as u32,
+            image::ColorType::Rgba8,
+            image::ImageFormat::Png,
+        )
+    }
+
+    //framebuffer the size of the camera's 3D view
+    pub fn for_view(camera: &Camera) -> Self {
+        Self::new(camera.width as usize, camera.height as usize)
+    }
+
+    pub fn clear(&mut self, color: [u8; 4]) {
+        for px in self.pixels.chunks_exact_mut(4) {
+            px.copy_from_slice(&color);
+        }
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let i = (y * self.width + x) * 4;
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+
+    //blends a pixel over what is already there, anything off the image is ignored
+    pub fn put_pixel(&mut self, x: i64, y: i64, color: [u8; 4]) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let (x, y) = (x as usize, y as usize);
+        let out = blend(self.get_pixel(x, y), color);
+        let i = (y * self.width + x) * 4;
+        self.pixels[i..i + 4].copy_from_slice(&out);
+    }
+
+    //texel lookup with u and v from 0.0 to 1.0, wraps around like a tiled texture
+    pub fn sample(&self, u: f64, v: f64) -> [u8; 4] {
+        let x = ((u.rem_euclid(1.0) * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v.rem_euclid(1.0) * self.height as f64) as usize).min(self.height - 1);
+        self.get_pixel(x, y)
+    }
+}
+
+//draws the 3D view of the world into fb and returns the depth buffer
+//fb should be the size of the camera's view (see Bitmap::for_view)
+//walls, floors and ceilings get their textures from the registry by tile id, same as the window
+pub fn render_view(
+    world: &World,
+    camera: &Camera,
+    shading: &Shading,
+    fb: &mut Framebuffer,
+    textures: &TextureRegistry,
+) -> Vec<f64> {
+    fb.clear(to_rgba8(BACKGROUND_COLOR));
+    draw_floor_and_ceiling(world, camera, shading, fb, textures);
+
+    let strips = view::cast_walls(&world.map, &world.player, camera);
+    let depth = view::depth_buffer(&strips);
+
+    for strip in strips.iter().flatten() {
+        let light = scale_light(
+            world.wall_light(strip.cell, strip.face),
+            shading.side_shade(strip.side),
+        );
+        draw_wall_strip(fb, strip, textures, shading, light, world.map.tile_size);
+    }
+
+    //sprites go far to near so nearer ones paint over, and are cut into one slice per ray
+    //column so walls hide exactly the columns they cover
+    let tile_size = world.map.tile_size;
+    for (sprite, rect) in view::sorted_sprites(world, camera) {
+        let region = match textures.sprite_frame(sprite.name, sprite.animation, sprite.view_angle) {
+            Some(region) => region,
+            None => continue,
+        };
+        let slices = view::sprite_slices(&rect, &depth, camera);
+        let light = world.light_at(sprite.x, sprite.y);
+        draw_sprite(fb, &rect, &slices, textures, region, |texel| {
+            shading.apply_rgba8(texel, light, rect.depth, tile_size)
+        });
+    }
+
+    if let Some(tint) = view::damage_tint(world) {
+        let tint = to_rgba8(tint);
+        for y in 0..fb.height as i64 {
+            for x in 0..fb.width as i64 {
+                fb.put_pixel(x, y, tint);
+            }
+        }
+    }
+
+    depth
+}
+
+//FLOOR AND CEILING CASTING
+//every pixel above or below the horizon is traced back to the spot of floor or ceiling it shows
+//tiles with texture id 0 are left alone, walls drawn afterwards paint over the rest
+pub fn draw_floor_and_ceiling(
+    world: &World,
+    camera: &Camera,
+    shading: &Shading,
+    fb: &mut Framebuffer,
+    textures: &TextureRegistry,
+) {
+    let map = &world.map;
+    let player = &world.player;
+    let ts = map.tile_size;
+    let horizon = projection::horizon(camera);
+
+    //direction of the camera ray through the middle of each pixel column
+    let columns: Vec<(f64, f64)> = (0..fb.width)
+        .map(|x| camera.ray_dir(player, (x as f64 + 0.5) / fb.width as f64))
+        .collect();
+
+    for y in 0..fb.height {
+        let from_horizon = y as f64 + 0.5 - horizon; //positive below the horizon (floor)
+        let is_floor = from_horizon > 0.0;
+        let dist = projection::row_distance(camera, ts, from_horizon.abs());
+
+        for (x, &(dx, dy)) in columns.iter().enumerate() {
+            //camera rays move one unit forward per unit along them, so the row distance is used as is
+            let wx = player.px + dx * dist;
+            let wy = player.py + dy * dist;
+            let (tx, ty) = map.to_tile(wx, wy);
+
+            let id = if is_floor {
+                map.floor(tx, ty)
+            } else {
+                map.ceiling(tx, ty)
+            };
+            let id = match id {
+                Some(id) if id != 0 => id,
+                _ => continue,
+            };
+
+            let color = textures.sample(id, wx / ts, wy / ts);
+            let light = world.light_map.tile(tx, ty);
+            let color = shading.apply_rgba8(color, light, dist, ts);
+            fb.put_pixel(x as i64, y as i64, color);
+        }
+    }
+}
+
+//stretches one column of the wall's texture over the strip, clipped to the framebuffer
+fn draw_wall_strip(
+    fb: &mut Framebuffer,
+    strip: &WallStrip,
+    textures: &TextureRegistry,
+    shading: &Shading,
+    light: [f32; 3],
+    tile_size: f64,
+) {
+    let x0 = strip.x.floor() as i64;
+    let x1 = (strip.x + strip.width).floor() as i64;
+    let y0 = strip.top.floor().max(0.0) as i64;
+    let y1 = (strip.top + strip.height).ceil().min(fb.height as f64) as i64;
+
+    for y in y0..y1 {
+        let v = (y as f64 + 0.5 - strip.top) / strip.height; //how far down the wall this row is
+        if !(0.0..1.0).contains(&v) {
+            continue;
+        }
+        let color = textures.sample(strip.tile, strip.tex_u, v);
+        let color = shading.apply_rgba8(color, light, strip.depth, tile_size);
+        for x in x0..x1 {
+            fb.put_pixel(x, y, color);
+        }
+    }
+}
+
+//stretches a sprite's texture over the visible slices of its box, see through texels are skipped
+//shade lights and fogs each texel before it is blended in
+fn draw_sprite<F>(
+    fb: &mut Framebuffer,
+    rect: &SpriteRect,
+    slices: &[SpriteSlice],
+    textures: &TextureRegistry,
+    region: Region,
+    shade: F,
+) where
+    F: Fn([u8; 4]) -> [u8; 4],
+{
+    let y0 = rect.y.floor().max(0.0) as i64;
+    let y1 = (rect.y + rect.h).ceil().min(fb.height as f64) as i64;
+
+    for slice in slices {
+        //pixels whose middle is inside the slice
+        let x0 = (slice.x - 0.5).ceil() as i64;
+        let x1 = (slice.x + slice.w - 0.5).ceil() as i64;
+        for x in x0..x1 {
+            let u = (x as f64 + 0.5 - rect.x) / rect.w;
+            for y in y0..y1 {
+                let v = (y as f64 + 0.5 - rect.y) / rect.h;
+                if !(0.0..1.0).contains(&v) {
+                    continue;
+                }
+                let texel = textures.sample_region(region, u, v);
+                if texel[3] == 0 {
+                    continue;
+                }
+                fb.put_pixel(x, y, shade(texel));
+            }
+        }
+    }
+}
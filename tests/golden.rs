@@ -0,0 +1,323 @@
+//GOLDEN IMAGE TESTS
+//renders fixed camera positions with the software renderer and compares them to checked in PNGs
+//run with UPDATE_GOLDEN=1 to rewrite the reference images after an intended change
+
+use rust_raycaster::animation::{AnimKind, Animator, SpriteSheet};
+use rust_raycaster::level::Level;
+use rust_raycaster::shading::Shading;
+use rust_raycaster::soft::{self, Bitmap};
+use rust_raycaster::textures::TextureRegistry;
+use rust_raycaster::view::{self, Camera};
+use rust_raycaster::world::{deg_to_rad, Projectile, World};
+use std::path::{Path, PathBuf};
+
+//a pixel counts as different when any channel is off by more than this
+const CHANNEL_TOLERANCE: u8 = 8;
+//fraction of pixels allowed to differ before the test fails
+const MAX_DIFF_FRACTION: f64 = 0.005;
+
+//paths in the tests are relative to the crate root
+fn crate_path(path: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(path)
+}
+
+//textures are made in code so changing the game art doesn't break the tests
+//stripes along u and v make both texture mapping directions visible
+fn stripes(a: [u8; 4], b: [u8; 4], size: usize) -> Bitmap {
+    let mut tex = Bitmap::new(size, size);
+    for y in 0..size {
+        for x in 0..size {
+            let on = (x / 8 + y / 16) % 2 == 0;
+            let mut color = if on { a } else { b };
+            if x == 0 || y == 0 {
+                color = [0, 0, 0, 255]; //dark edge marks where each tile starts
+            }
+            tex.put_pixel(x as i64, y as i64, color);
+        }
+    }
+    tex
+}
+
+fn world_at(map_file: &str, x: f64, y: f64, angle: f64) -> World {
+    let level = Level::load(crate_path(map_file)).expect("test map should load");
+    let mut world = World::from_level(level);
+    world.player.px = x;
+    world.player.py = y;
+    world.player.pa = angle;
+    world.player.pdx = deg_to_rad(angle).cos();
+    world.player.pdy = -deg_to_rad(angle).sin();
+    world
+}
+
+fn render(world: &World) -> Bitmap {
+    render_with(world, &Camera::default(), &Shading::default())
+}
+
+//both test textures side by side in one atlas, so regions other than the first cell get covered
+fn test_textures() -> TextureRegistry {
+    let red = stripes([200, 60, 40, 255], [150, 40, 30, 255], 64);
+    let blue = stripes([60, 80, 200, 255], [200, 200, 220, 255], 64);
+    let mut atlas = Bitmap::new(128, 64);
+    for y in 0..64 {
+        for x in 0..64 {
+            atlas.put_pixel(x as i64, y as i64, red.get_pixel(x, y));
+            atlas.put_pixel(x as i64 + 64, y as i64, blue.get_pixel(x, y));
+        }
+    }
+
+    //enemy is a tall purple blob standing on the bottom of its cell, the bullet a small white dot,
+    //the health pickup a green blob and the barrel a wide grey one
+    let mut sprites = Bitmap::new(256, 64);
+    blob(&mut sprites, (32.0, 36.0), (14.0, 27.0), [150, 0, 150, 255]);
+    blob(&mut sprites, (96.0, 32.0), (8.0, 8.0), [255, 255, 255, 255]);
+    blob(
+        &mut sprites,
+        (160.0, 44.0),
+        (16.0, 12.0),
+        [40, 200, 60, 255],
+    );
+    blob(
+        &mut sprites,
+        (224.0, 36.0),
+        (24.0, 27.0),
+        [140, 140, 130, 255],
+    );
+
+    let mut textures = TextureRegistry::new();
+    let walls = textures.add_atlas("walls", atlas, 64);
+    textures.add_texture(1, walls, 0);
+    textures.add_texture(2, walls, 1);
+    let things = textures.add_atlas("things", sprites, 64);
+    textures.add_sprite(view::ENEMY_SPRITE, things, 0);
+    textures.add_sprite(view::BULLET_SPRITE, things, 1);
+    textures.add_sprite("health", things, 2);
+    textures.add_sprite("barrel", things, 3);
+
+    //enemy animations: idle is the same blob as the still sprite, death squashes it flat
+    //each row is the enemy seen from the next side round, with a yellow eye that moves
+    //across as it turns and is hidden from behind, death looks the same from every side
+    let mut sheet = Bitmap::new(192, 512);
+    for side in 0..8 {
+        let y = side as f64 * 64.0;
+        let turn = (side as f64 * 45.0).to_radians();
+        blob(
+            &mut sheet,
+            (32.0, y + 36.0),
+            (14.0, 27.0),
+            [150, 0, 150, 255],
+        );
+        if turn.cos() > -0.5 {
+            let eye = (32.0 - 9.0 * turn.sin(), y + 20.0);
+            blob(&mut sheet, eye, (4.0, 4.0), [255, 230, 0, 255]);
+        }
+        blob(
+            &mut sheet,
+            (96.0, y + 48.0),
+            (20.0, 15.0),
+            [150, 0, 150, 255],
+        );
+        blob(
+            &mut sheet,
+            (160.0, y + 58.0),
+            (28.0, 5.0),
+            [150, 0, 150, 255],
+        );
+    }
+    let anims =
+        "image sheet.png\ncell 64\nrotations 8 3\nanim idle 1 loop 0\nanim death 10 once 1 2\n";
+    let anims = SpriteSheet::parse(anims).unwrap();
+    textures
+        .add_sheet(view::ENEMY_SPRITE, sheet, anims)
+        .unwrap();
+    textures
+}
+
+//filled ellipse with a dark outline, so the sprite's edges and any clipping are easy to see
+fn blob(bitmap: &mut Bitmap, center: (f64, f64), radius: (f64, f64), color: [u8; 4]) {
+    for y in 0..bitmap.height {
+        for x in 0..bitmap.width {
+            let dx = (x as f64 + 0.5 - center.0) / radius.0;
+            let dy = (y as f64 + 0.5 - center.1) / radius.1;
+            let d = dx * dx + dy * dy;
+            if d <= 0.7 {
+                bitmap.put_pixel(x as i64, y as i64, color);
+            } else if d <= 1.0 {
+                bitmap.put_pixel(x as i64, y as i64, [20, 0, 20, 255]);
+            }
+        }
+    }
+}
+
+fn render_with(world: &World, camera: &Camera, shading: &Shading) -> Bitmap {
+    let mut frame = Bitmap::for_view(camera);
+    soft::render_view(world, camera, shading, &mut frame, &test_textures());
+    frame
+}
+
+//compares against tests/golden/<name>.png, writing the actual frame next to the build output on failure
+fn check_golden(name: &str, frame: &Bitmap) {
+    let golden_path = crate_path("tests/golden").join(format!("{}.png", name));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        frame.save_png(&golden_path).unwrap();
+        return;
+    }
+
+    let golden = Bitmap::load(&golden_path).unwrap_or_else(|e| {
+        panic!(
+            "missing golden image {} ({}), run with UPDATE_GOLDEN=1 to create it",
+            golden_path.display(),
+            e
+        )
+    });
+    assert_eq!(
+        (golden.width, golden.height),
+        (frame.width, frame.height),
+        "{}: frame size changed",
+        name
+    );
+
+    let differing = golden
+        .pixels
+        .chunks_exact(4)
+        .zip(frame.pixels.chunks_exact(4))
+        .filter(|(a, b)| {
+            a.iter()
+                .zip(b.iter())
+                .any(|(x, y)| x.abs_diff(*y) > CHANNEL_TOLERANCE)
+        })
+        .count();
+    let fraction = differing as f64 / (frame.width * frame.height) as f64;
+
+    if fraction > MAX_DIFF_FRACTION {
+        let actual = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.actual.png", name));
+        frame.save_png(&actual).unwrap();
+        panic!(
+            "{}: {:.2}% of pixels differ from {} (allowed {:.2}%), actual frame saved to {}",
+            name,
+            fraction * 100.0,
+            golden_path.display(),
+            MAX_DIFF_FRACTION * 100.0,
+            actual.display()
+        );
+    }
+}
+
+#[test]
+fn level1_start_facing_up() {
+    let world = world_at("tests/maps/level1.map", 160.0, 416.0, 90.0);
+    check_golden("level1_start_facing_up", &render(&world));
+}
+
+#[test]
+fn level1_diagonal_corner() {
+    //looks across the room at a 45 degree angle so both wall sides show up
+    let world = world_at("tests/maps/level1.map", 160.0, 416.0, 45.0);
+    check_golden("level1_diagonal_corner", &render(&world));
+}
+
+#[test]
+fn level1_close_to_wall() {
+    //wall taller than the view, checks clipping and vertical texture mapping
+    let world = world_at("tests/maps/level1.map", 96.0, 300.0, 180.0);
+    check_golden("level1_close_to_wall", &render(&world));
+}
+
+#[test]
+fn level1_enemy_in_view() {
+    //enemy at tile (5, 2) straight ahead
+    let world = world_at("tests/maps/level1.map", 352.0, 224.0, 90.0);
+    check_golden("level1_enemy_in_view", &render(&world));
+}
+
+#[test]
+fn level1_wide_fov_per_column() {
+    //one ray per pixel column with a 90 degree view, the enemy has to stay in its column
+    let world = world_at("tests/maps/level1.map", 352.0, 224.0, 90.0);
+    let camera = Camera::per_column(90.0, 640, 400);
+    check_golden(
+        "level1_wide_fov_per_column",
+        &render_with(&world, &camera, &Shading::default()),
+    );
+}
+
+#[test]
+fn hall_small_tiles() {
+    //16x8 map with 32px tiles, looking down its length at an enemy between pillars
+    let world = world_at("tests/maps/hall.map", 48.0, 144.0, 0.0);
+    check_golden("hall_small_tiles", &render(&world));
+}
+
+#[test]
+fn hall_thick_green_fog() {
+    //same view as hall_small_tiles, the far end of the hall should be lost in green fog
+    let world = world_at("tests/maps/hall.map", 48.0, 144.0, 0.0);
+    let shading = Shading {
+        fog_color: [0.1, 0.4, 0.1, 1.0],
+        fog_density: 0.4,
+        ..Shading::default()
+    };
+    check_golden(
+        "hall_thick_green_fog",
+        &render_with(&world, &Camera::default(), &shading),
+    );
+}
+
+#[test]
+fn hall_muzzle_flash() {
+    //a shot lights up the dim end of the hall around the player
+    let mut world = world_at("tests/maps/hall.map", 400.0, 100.0, 180.0);
+    world.shoot();
+    check_golden("hall_muzzle_flash", &render(&world));
+}
+
+#[test]
+fn hall_enemy_half_behind_pillar() {
+    //one ray per pixel column, the pillar at (3, 3) cuts the enemy off partway across
+    let world = world_at("tests/maps/hall.map", 72.0, 140.0, 0.0);
+    let camera = Camera::per_column(60.0, 480, 512);
+    check_golden(
+        "hall_enemy_half_behind_pillar",
+        &render_with(&world, &camera, &Shading::default()),
+    );
+}
+
+#[test]
+fn hall_bullet_behind_enemy() {
+    //a bullet just behind the enemy must be partly hidden by it, not painted over it
+    let mut world = world_at("tests/maps/hall.map", 144.0, 112.0, 0.0);
+    let mut shot = Projectile::player_shot(&world.player);
+    shot.x = 272.0;
+    shot.y = 120.0;
+    world.projectiles.spawn(shot);
+    check_golden("hall_bullet_behind_enemy", &render(&world));
+}
+
+#[test]
+fn level1_enemy_dying() {
+    //enemy straight ahead was shot a moment ago and is halfway through its death animation
+    let mut world = world_at("tests/maps/level1.map", 352.0, 224.0, 90.0);
+    for enemy in &mut world.enemies {
+        enemy.alive = false;
+        enemy.animation = Animator {
+            kind: AnimKind::Death,
+            time: 0.05,
+        };
+    }
+    check_golden("level1_enemy_dying", &render(&world));
+}
+
+#[test]
+fn hall_enemy_seen_from_side() {
+    //enemy faces the player start, so looking down on it from the wall above shows its side
+    let world = world_at("tests/maps/hall.map", 240.0, 44.0, 270.0);
+    check_golden("hall_enemy_seen_from_side", &render(&world));
+}
+
+#[test]
+fn level1_game_over() {
+    //once the player is dead the view is washed dark red
+    let mut world = world_at("tests/maps/level1.map", 160.0, 416.0, 90.0);
+    world.player.health.hp = 0.0;
+    check_golden("level1_game_over", &render(&world));
+}
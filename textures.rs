@@ -0,0 +1,471 @@
+//TEXTURES
+//maps wall, floor and ceiling ids and named sprites to square cells of one or more atlas images
+//the manifest is plain text like the level files so new textures need no code changes
+//
+//  // comment lines start with two slashes
+//  atlas walls walls.png 64    loads an image and splits it into 64x64 cells, row by row
+//  texture 1 walls 0           tile id 1 uses cell 0 of the walls atlas
+//  texture 2 walls 3           tile id 2 uses cell 3 (second row if the image is 3 cells wide)
+//  sprite enemy things 0       the enemy billboard uses cell 0 of the things atlas
+//
+//image paths are relative to the folder the manifest is in, sprites are see through where alpha is 0
+//ids and sprites without a texture, and textures whose image could not be loaded, get a checkerboard
+//animated sprites come from sprite sheets (see animation.rs) added with add_sheet
+
+use crate::animation::{AnimKind, Animator, SheetError, SpriteSheet};
+use crate::parse::{expect_args, parse_arg, parse_err, LineError};
+use crate::soft::Bitmap;
+use std::fmt;
+
+//checkerboard drawn for ids that have no texture, loud enough to notice in game
+pub const FALLBACK_ATLAS: &str = "fallback";
+const FALLBACK_SIZE: usize = 64;
+const FALLBACK_COLORS: [[u8; 4]; 2] = [[255, 0, 255, 255], [40, 0, 40, 255]];
+//the checkerboard is the whole of atlas 0
+const FALLBACK_REGION: Region = Region {
+    atlas: 0,
+    x: 0,
+    y: 0,
+    size: FALLBACK_SIZE,
+};
+
+//square checkerboard of 8x8 squares in two colors
+pub fn checkerboard(size: usize, a: [u8; 4], b: [u8; 4]) -> Bitmap {
+    let mut bitmap = Bitmap::new(size, size);
+    for y in 0..size {
+        for x in 0..size {
+            let color = if (x / 8 + y / 8) % 2 == 0 { a } else { b };
+            bitmap.put_pixel(x as i64, y as i64, color);
+        }
+    }
+    bitmap
+}
+
+//ONE ATLAS IMAGE
+pub struct Atlas {
+    pub name: String,
+    pub bitmap: Bitmap,
+    pub cell_size: usize, //width and height of one texture in the atlas
+}
+
+impl Atlas {
+    //cells across one row of the image
+    pub fn columns(&self) -> usize {
+        self.bitmap.width / self.cell_size
+    }
+
+    //cells in the whole image
+    pub fn cell_count(&self) -> usize {
+        self.columns() * (self.bitmap.height / self.cell_size)
+    }
+}
+
+//where one texture sits, in pixels of its atlas
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub atlas: usize, //index into TextureRegistry::atlases
+    pub x: usize,
+    pub y: usize,
+    pub size: usize,
+}
+
+//ERRORS
+#[derive(Debug)]
+pub enum TextureError {
+    //manifest is malformed
+    Parse { line: usize, msg: String },
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::Parse { line, msg } => write!(f, "line {}: {}", line, msg),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+impl From<LineError> for TextureError {
+    fn from((line, msg): LineError) -> Self {
+        TextureError::Parse { line, msg }
+    }
+}
+
+//TEXTURE REGISTRY
+//atlas 0 is always the fallback checkerboard
+pub struct TextureRegistry {
+    pub atlases: Vec<Atlas>,
+    regions: Vec<(i32, Region)>, //tile id -> region, few enough that a list is fine
+    sprites: Vec<(String, Region)>, //sprite name -> region
+    sheets: Vec<(String, usize, SpriteSheet)>, //sprite name -> atlas with its frames and animations
+}
+
+impl TextureRegistry {
+    //registry with only the fallback checkerboard in it
+    pub fn new() -> Self {
+        let mut registry = Self {
+            atlases: Vec::new(),
+            regions: Vec::new(),
+            sprites: Vec::new(),
+            sheets: Vec::new(),
+        };
+        let [a, b] = FALLBACK_COLORS;
+        registry.add_atlas(
+            FALLBACK_ATLAS,
+            checkerboard(FALLBACK_SIZE, a, b),
+            FALLBACK_SIZE,
+        );
+        registry
+    }
+
+    //builds the registry from manifest text, load_image turns an atlas file name into its pixels
+    //load_image can return None to skip an image, textures in that atlas then use the fallback
+    pub fn parse<F>(text: &str, mut load_image: F) -> Result<Self, TextureError>
+    where
+        F: FnMut(&str) -> Option<Bitmap>,
+    {
+        let mut registry = Self::new();
+        let mut skipped: Vec<&str> = Vec::new(); //atlases whose image was not loaded
+        let mut defined: Vec<i32> = Vec::new(); //ids seen so far, including ones left to the fallback
+        let mut named: Vec<&str> = Vec::new(); //same for sprite names
+
+        for (i, raw) in text.lines().enumerate() {
+            let line_no = i + 1; //editors count lines from 1
+            let line = raw.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            let key = words.next().unwrap_or("");
+            let args: Vec<&str> = words.collect();
+
+            match key {
+                "atlas" => {
+                    expect_args(line_no, &args, 3, "atlas <name> <file> <cell size>")?;
+                    let name = args[0];
+                    if registry.atlas_index(name).is_some() || skipped.contains(&name) {
+                        return Err(parse_err(
+                            line_no,
+                            format!("atlas `{}` is defined twice", name),
+                        ));
+                    }
+                    let cell_size: usize = parse_arg(line_no, "cell size", args[2])?;
+                    if cell_size == 0 {
+                        return Err(parse_err(line_no, "cell size must be positive".to_string()));
+                    }
+                    let bitmap = match load_image(args[1]) {
+                        Some(bitmap) => bitmap,
+                        None => {
+                            skipped.push(name);
+                            continue;
+                        }
+                    };
+                    if bitmap.width < cell_size || bitmap.height < cell_size {
+                        return Err(parse_err(
+                            line_no,
+                            format!(
+                                "{} is {}x{}, smaller than one {} pixel cell",
+                                args[1], bitmap.width, bitmap.height, cell_size
+                            ),
+                        ));
+                    }
+                    registry.add_atlas(name, bitmap, cell_size);
+                }
+                "texture" => {
+                    expect_args(line_no, &args, 3, "texture <id> <atlas> <cell>")?;
+                    let id: i32 = parse_arg(line_no, "texture id", args[0])?;
+                    if id <= 0 {
+                        return Err(parse_err(
+                            line_no,
+                            format!("texture id must be positive (0 is empty), got {}", id),
+                        ));
+                    }
+                    if defined.contains(&id) {
+                        return Err(parse_err(
+                            line_no,
+                            format!("texture {} is defined twice", id),
+                        ));
+                    }
+                    defined.push(id);
+                    if skipped.contains(&args[1]) {
+                        continue; //left to the fallback
+                    }
+                    let (atlas, cell) = registry.parse_cell(line_no, args[1], args[2])?;
+                    registry.add_texture(id, atlas, cell);
+                }
+                "sprite" => {
+                    expect_args(line_no, &args, 3, "sprite <name> <atlas> <cell>")?;
+                    let name = args[0];
+                    if named.contains(&name) {
+                        return Err(parse_err(
+                            line_no,
+                            format!("sprite `{}` is defined twice", name),
+                        ));
+                    }
+                    named.push(name);
+                    if skipped.contains(&args[1]) {
+                        continue; //left to the fallback
+                    }
+                    let (atlas, cell) = registry.parse_cell(line_no, args[1], args[2])?;
+                    registry.add_sprite(name, atlas, cell);
+                }
+                _ => return Err(parse_err(line_no, format!("unknown keyword `{}`", key))),
+            }
+        }
+
+        Ok(registry)
+    }
+
+    //atlas name and cell number from a manifest line, checked against the atlases loaded so far
+    fn parse_cell(
+        &self,
+        line: usize,
+        atlas: &str,
+        cell: &str,
+    ) -> Result<(usize, usize), TextureError> {
+        let index = self
+            .atlas_index(atlas)
+            .ok_or_else(|| (line, format!("unknown atlas `{}`", atlas)))?;
+        let cell: usize = parse_arg(line, "cell", cell)?;
+        let count = self.atlases[index].cell_count();
+        if cell >= count {
+            return Err(parse_err(
+                line,
+                format!(
+                    "atlas `{}` only has {} cells, got cell {}",
+                    atlas, count, cell
+                ),
+            ));
+        }
+        Ok((index, cell))
+    }
+
+    //adds an atlas and returns its index for add_texture
+    pub fn add_atlas(&mut self, name: &str, bitmap: Bitmap, cell_size: usize) -> usize {
+        self.atlases.push(Atlas {
+            name: name.to_string(),
+            bitmap,
+            cell_size,
+        });
+        self.atlases.len() - 1
+    }
+
+    //where a cell of an atlas is in its image
+    fn cell_region(&self, atlas: usize, cell: usize) -> Region {
+        let a = &self.atlases[atlas];
+        let columns = a.columns();
+        Region {
+            atlas,
+            x: (cell % columns) * a.cell_size,
+            y: (cell / columns) * a.cell_size,
+            size: a.cell_size,
+        }
+    }
+
+    //points tile id at a cell of an atlas, replacing any texture it had
+    pub fn add_texture(&mut self, id: i32, atlas: usize, cell: usize) {
+        let region = self.cell_region(atlas, cell);
+        self.regions.retain(|&(i, _)| i != id);
+        self.regions.push((id, region));
+    }
+
+    //points a sprite name at a cell of an atlas, replacing any texture it had
+    pub fn add_sprite(&mut self, name: &str, atlas: usize, cell: usize) {
+        let region = self.cell_region(atlas, cell);
+        self.sprites.retain(|(n, _)| n != name);
+        self.sprites.push((name.to_string(), region));
+    }
+
+    fn atlas_index(&self, name: &str) -> Option<usize> {
+        self.atlases.iter().position(|a| a.name == name)
+    }
+
+    //where the texture for a tile id is, None if it has none
+    pub fn region(&self, id: i32) -> Option<Region> {
+        self.regions
+            .iter()
+            .find(|&&(i, _)| i == id)
+            .map(|&(_, region)| region)
+    }
+
+    //where to draw a tile id from, the fallback checkerboard if it has no texture
+    pub fn region_or_fallback(&self, id: i32) -> Region {
+        self.region(id).unwrap_or(FALLBACK_REGION)
+    }
+
+    //where a sprite's texture is, the fallback checkerboard if it has none
+    pub fn sprite_region(&self, name: &str) -> Region {
+        self.sprites
+            .iter()
+            .find(|(n, _)| n == name)
+            .map_or(FALLBACK_REGION, |&(_, region)| region)
+    }
+
+    //adds a sprite sheet as a new atlas and animates the named sprite with it
+    pub fn add_sheet(
+        &mut self,
+        name: &str,
+        bitmap: Bitmap,
+        sheet: SpriteSheet,
+    ) -> Result<usize, SheetError> {
+        let size = sheet.cell_size;
+        let cells = (bitmap.width / size) * (bitmap.height / size);
+        let needed = sheet.cells_needed();
+        if cells < needed {
+            return Err(SheetError::TooSmall { cells, needed });
+        }
+        let atlas = self.add_atlas(name, bitmap, size);
+        self.sheets.retain(|(n, _, _)| n != name);
+        self.sheets.push((name.to_string(), atlas, sheet));
+        Ok(atlas)
+    }
+
+    //region to draw a sprite with right now, None if there is nothing to show
+    //sprites with a sheet show the current frame of their animation (idle if the sheet lacks it),
+    //others show their still texture, except that a dying sprite with no death frames vanishes
+    //view_angle is which side of the sprite the camera sees, in degrees counterclockwise from its
+    //front, and picks the closest rotation on sheets that have them
+    pub fn sprite_frame(
+        &self,
+        name: &str,
+        animator: Option<Animator>,
+        view_angle: f64,
+    ) -> Option<Region> {
+        let sheet = self.sheets.iter().find(|(n, _, _)| n == name);
+        match (animator, sheet) {
+            (Some(animator), Some((_, atlas, sheet))) => {
+                let anim = match sheet.animation(animator.kind) {
+                    Some(anim) => anim,
+                    None if animator.kind == AnimKind::Death => return None,
+                    None => sheet.animation(AnimKind::Idle)?,
+                };
+                let cell = anim.cells[anim.frame(animator.time)];
+                let cell = sheet.rotated_cell(cell, view_angle);
+                Some(self.cell_region(*atlas, cell))
+            }
+            (Some(animator), None) if animator.kind == AnimKind::Death => None,
+            _ => Some(self.sprite_region(name)),
+        }
+    }
+
+    //texel of a tile id's texture with u and v from 0.0 to 1.0, wraps around inside its cell
+    pub fn sample(&self, id: i32, u: f64, v: f64) -> [u8; 4] {
+        self.sample_region(self.region_or_fallback(id), u, v)
+    }
+
+    //texel of any region with u and v from 0.0 to 1.0, wraps around inside it
+    pub fn sample_region(&self, region: Region, u: f64, v: f64) -> [u8; 4] {
+        let size = region.size as f64;
+        let x = ((u.rem_euclid(1.0) * size) as usize).min(region.size - 1);
+        let y = ((v.rem_euclid(1.0) * size) as usize).min(region.size - 1);
+        self.atlases[region.atlas]
+            .bitmap
+            .get_pixel(region.x + x, region.y + y)
+    }
+}
+
+impl Default for TextureRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::line_error;
+
+    //every atlas image is 128x64, two 64 pixel cells side by side
+    fn parse(text: &str) -> Result<TextureRegistry, TextureError> {
+        TextureRegistry::parse(text, |_| Some(Bitmap::new(128, 64)))
+    }
+
+    //line number and message of the error a manifest fails with
+    fn parse_error(text: &str) -> LineError {
+        line_error(parse(text))
+    }
+
+    #[test]
+    fn maps_ids_and_sprites_to_cells() {
+        let registry =
+            parse("atlas walls w.png 64\ntexture 1 walls 1\nsprite enemy walls 0\n").unwrap();
+        let walls = Region {
+            atlas: 1,
+            x: 64,
+            y: 0,
+            size: 64,
+        };
+        assert_eq!(registry.region(1), Some(walls));
+        assert_eq!(registry.sprite_region("enemy").x, 0);
+        assert_eq!(registry.region(2), None);
+        assert_eq!(registry.region_or_fallback(2), FALLBACK_REGION);
+        assert_eq!(registry.sprite_region("missing"), FALLBACK_REGION);
+    }
+
+    #[test]
+    fn skipped_images_leave_their_textures_to_the_fallback() {
+        let text = "atlas walls w.png 64\ntexture 1 walls 1\n";
+        let registry = TextureRegistry::parse(text, |_| None).unwrap();
+        assert_eq!(registry.region_or_fallback(1), FALLBACK_REGION);
+    }
+
+    #[test]
+    fn duplicate_atlases_ids_and_sprites() {
+        assert_eq!(
+            parse_error("atlas walls a.png 64\natlas walls b.png 64\n"),
+            (2, "atlas `walls` is defined twice".to_string())
+        );
+        assert_eq!(
+            parse_error("atlas walls w.png 64\ntexture 1 walls 0\n\ntexture 1 walls 1\n"),
+            (4, "texture 1 is defined twice".to_string())
+        );
+        assert_eq!(
+            parse_error("atlas walls w.png 64\nsprite lamp walls 0\nsprite lamp walls 1\n"),
+            (3, "sprite `lamp` is defined twice".to_string())
+        );
+    }
+
+    #[test]
+    fn cell_past_the_end_of_the_atlas() {
+        assert_eq!(
+            parse_error("atlas walls w.png 64\ntexture 1 walls 2\n"),
+            (2, "atlas `walls` only has 2 cells, got cell 2".to_string())
+        );
+    }
+
+    #[test]
+    fn unknown_atlas() {
+        assert_eq!(
+            parse_error("// nothing loaded yet\ntexture 1 walls 0\n"),
+            (2, "unknown atlas `walls`".to_string())
+        );
+    }
+
+    #[test]
+    fn bad_ids_and_cell_sizes() {
+        assert_eq!(
+            parse_error("atlas walls w.png 64\ntexture 0 walls 0\n"),
+            (
+                2,
+                "texture id must be positive (0 is empty), got 0".to_string()
+            )
+        );
+        assert_eq!(
+            parse_error("atlas walls w.png 0\n"),
+            (1, "cell size must be positive".to_string())
+        );
+        assert_eq!(
+            parse_error("atlas walls w.png 256\n"),
+            (
+                1,
+                "w.png is 128x64, smaller than one 256 pixel cell".to_string()
+            )
+        );
+        assert_eq!(
+            parse_error("tile 1 walls 0\n"),
+            (1, "unknown keyword `tile`".to_string())
+        );
+    }
+}
@@ -0,0 +1,369 @@
+//3D VIEW
+//works out where walls and sprites land in the 3D view, shared by the window and software renderers
+//all x and y values here are relative to the top left of the 3D view, not the window
+
+use crate::animation::{AnimKind, Animator};
+use crate::map::Map;
+use crate::projection;
+use crate::raycast::{cast_ray_dir, Face, Side};
+use crate::world::{deg_to_rad, Player, World, PAIN_FLASH};
+
+//default camera: 60 degree field of view, 60 rays, 8px per column
+pub const FOV: f64 = 60.0;
+pub const NUM_RAYS: usize = 60;
+pub const WALL_STRIP_WIDTH: f64 = 8.0;
+pub const VIEW_W: f64 = NUM_RAYS as f64 * WALL_STRIP_WIDTH; //width of the 3D view in pixels
+pub const VIEW_H: f64 = 512.0; //height of the 3D view in pixels
+
+//CAMERA
+//field of view, ray count and size of the 3D view, every column mapping is worked out from these
+//rays go through evenly spaced points on a camera plane in front of the player, not evenly spaced
+//angles, so columns line up with the screen and walls stay straight at any field of view
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub fov: f64,        //field of view in degrees
+    pub num_rays: usize, //rays cast across the view, each one draws a strip
+    pub width: f64,      //width of the 3D view in pixels
+    pub height: f64,     //height of the 3D view in pixels
+}
+
+impl Camera {
+    pub fn new(fov: f64, num_rays: usize, width: f64, height: f64) -> Self {
+        assert!(
+            fov > 0.0 && fov < 180.0,
+            "field of view must be between 0 and 180 degrees"
+        );
+        assert!(num_rays > 0, "camera needs at least one ray");
+        assert!(
+            width >= 1.0 && height >= 1.0,
+            "view must be at least one pixel"
+        );
+        Self {
+            fov,
+            num_rays,
+            width,
+            height,
+        }
+    }
+
+    //one ray for every pixel column of a view this size
+    pub fn per_column(fov: f64, width: usize, height: usize) -> Self {
+        Self::new(fov, width, width as f64, height as f64)
+    }
+
+    //how wide each ray's strip is on screen
+    pub fn strip_width(&self) -> f64 {
+        self.width / self.num_rays as f64
+    }
+
+    //half width of the camera plane one unit in front of the player
+    fn plane_scale(&self) -> f64 {
+        deg_to_rad(self.fov / 2.0).tan()
+    }
+
+    //camera plane vector, points to the right of the view direction
+    pub fn plane(&self, player: &Player) -> (f64, f64) {
+        let k = self.plane_scale();
+        (-player.pdy * k, player.pdx * k)
+    }
+
+    //direction of the ray through a position across the view (0.0 left edge, 1.0 right edge)
+    //its forward part is the unit view direction, so distances along it are perpendicular depths
+    pub fn ray_dir(&self, player: &Player, norm: f64) -> (f64, f64) {
+        let (plane_x, plane_y) = self.plane(player);
+        let camera_x = 2.0 * norm - 1.0; //-1.0 on the left edge, 1.0 on the right
+        (
+            player.pdx + plane_x * camera_x,
+            player.pdy + plane_y * camera_x,
+        )
+    }
+
+    //distance from the eye to the screen in pixels, a wall one tile away is tile_size times this tall
+    pub fn focal_length(&self) -> f64 {
+        (self.width / 2.0) / self.plane_scale()
+    }
+
+    //ray column a screen x position falls in, None if it is off the view
+    pub fn column_at(&self, x: f64) -> Option<usize> {
+        if x < 0.0 || x >= self.width {
+            return None;
+        }
+        Some(((x / self.strip_width()) as usize).min(self.num_rays - 1))
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new(FOV, NUM_RAYS, VIEW_W, VIEW_H)
+    }
+}
+
+//COLOR DEFINITIONS
+pub const BACKGROUND_COLOR: [f32; 4] = [0.3, 0.3, 0.3, 1.0];
+const PAIN_TINT: [f32; 4] = [1.0, 0.0, 0.0, 0.4]; //right after the player is hurt, fades out
+const GAME_OVER_TINT: [f32; 4] = [0.5, 0.0, 0.0, 0.6]; //stays once the player is dead
+
+//SPRITE NAMES
+//what the billboards are called in the texture manifest
+pub const ENEMY_SPRITE: &str = "enemy";
+pub const BULLET_SPRITE: &str = "bullet";
+
+//SPRITE SIZES
+//how tall each kind of sprite is compared to a wall, everything but bullets stands on the floor
+const ENEMY_SIZE: f64 = 0.75;
+const BULLET_SIZE: f64 = 0.2;
+const PICKUP_SIZE: f64 = 0.3;
+const DECORATION_SIZE: f64 = 0.5;
+//bullets fly at eye height, which is half a wall up
+const BULLET_LIFT: f64 = 0.5 - BULLET_SIZE / 2.0;
+
+//one textured wall column of the 3D view
+pub struct WallStrip {
+    pub x: f64,                //left edge of the strip
+    pub width: f64,            //on screen width of the strip
+    pub top: f64,              //top of the wall (can be above the view when close)
+    pub height: f64,           //on screen height of the wall
+    pub depth: f64,            //perpendicular distance to the wall (no fisheye)
+    pub tile: i32,             //wall id that was hit
+    pub side: Side,            //which kind of grid line was hit
+    pub face: Face,            //which face of the tile was hit
+    pub cell: (i32, i32),      //tile coordinates of the wall
+    pub tex_u: f64,            //where along the wall face the ray hit, 0.0 to 1.0
+    pub hit_point: (f64, f64), //world position of the hit, for drawing the ray on the map
+}
+
+//one billboard standing in the world
+pub struct Sprite<'a> {
+    pub name: &'a str,               //sprite name in the texture manifest
+    pub x: f64,                      //world position
+    pub y: f64,                      //world position
+    pub size: f64,                   //height compared to a wall at the same distance
+    pub lift: f64,                   //how far its bottom is above the floor, compared to a wall
+    pub animation: Option<Animator>, //frame to show if it has a sheet, None for still sprites
+    pub view_angle: f64,             //side the player sees, degrees counterclockwise from its front
+}
+
+//on screen box a sprite is drawn in, its texture is stretched over the whole box
+pub struct SpriteRect {
+    pub x: f64,
+    pub y: f64,
+    pub w: f64,
+    pub h: f64,
+    pub depth: f64, //perpendicular distance like the walls', used for fog and hiding it behind walls
+}
+
+//part of a sprite that lands in one ray column and is in front of the wall there
+pub struct SpriteSlice {
+    pub x: f64,  //left edge on screen
+    pub w: f64,  //on screen width
+    pub u0: f64, //texture column at the left edge, 0.0 to 1.0
+    pub u1: f64, //texture column at the right edge
+}
+
+//casts one ray per column, None where the ray left the map without hitting a wall
+pub fn cast_walls(map: &Map, player: &Player, camera: &Camera) -> Vec<Option<WallStrip>> {
+    let ts = map.tile_size; //world size of one grid cell
+    let strip_w = camera.strip_width();
+
+    //sweep the rays across the camera plane from left to right, through the middle of each strip
+    (0..camera.num_rays)
+        .map(|r| {
+            let norm = (r as f64 + 0.5) / camera.num_rays as f64;
+            let dir = camera.ray_dir(player, norm);
+
+            //find the first wall along the ray
+            //dir is not unit length, so the distance comes back already measured along the view direction
+            let hit = cast_ray_dir(map, (player.px, player.py), dir)?;
+
+            let depth = hit.distance;
+            let line_h = projection::wall_height(camera, ts, depth);
+            let line_off = projection::screen_y(camera, ts, depth, ts); //top of the wall
+
+            Some(WallStrip {
+                x: r as f64 * strip_w, //each ray has its own strip
+                width: strip_w,
+                top: line_off,
+                height: line_h,
+                depth,
+                tile: hit.tile,
+                side: hit.side,
+                face: hit.face,
+                cell: hit.cell,
+                tex_u: hit.tex_u,
+                hit_point: hit.hit_point,
+            })
+        })
+        .collect()
+}
+
+//distance to the wall in every column, columns with no wall are infinitely deep
+pub fn depth_buffer(strips: &[Option<WallStrip>]) -> Vec<f64> {
+    strips
+        .iter()
+        .map(|s| s.as_ref().map_or(f64::INFINITY, |s| s.depth))
+        .collect()
+}
+
+//every enemy, projectile, pickup and decoration in front of the camera this frame, with where it
+//lands on screen, sorted furthest first so drawing them in order paints near sprites over far ones
+//the order uses the same perpendicular depth the sprites are scaled and hidden behind walls by
+pub fn sorted_sprites<'a>(world: &'a World, camera: &Camera) -> Vec<(Sprite<'a>, SpriteRect)> {
+    let mut sprites = Vec::new();
+    //dead enemies stay around to play their death animation and lie there afterwards
+    for enemy in &world.enemies {
+        if !enemy.alive && enemy.animation.kind != AnimKind::Death {
+            continue;
+        }
+        //0 when the enemy looks straight at the player, 180 when it has its back to them
+        let to_player = enemy.angle_to(world.player.px, world.player.py);
+        sprites.push(Sprite {
+            name: ENEMY_SPRITE,
+            x: enemy.x,
+            y: enemy.y,
+            size: ENEMY_SIZE,
+            lift: 0.0,
+            animation: Some(enemy.animation),
+            view_angle: to_player - enemy.facing,
+        });
+    }
+    for projectile in world.projectiles.active() {
+        sprites.push(Sprite {
+            name: BULLET_SPRITE,
+            x: projectile.x,
+            y: projectile.y,
+            size: BULLET_SIZE,
+            lift: BULLET_LIFT,
+            animation: None,
+            view_angle: 0.0,
+        });
+    }
+    for pickup in world.pickups.iter().filter(|p| !p.taken) {
+        sprites.push(Sprite {
+            name: &pickup.sprite,
+            x: pickup.x,
+            y: pickup.y,
+            size: PICKUP_SIZE,
+            lift: 0.0,
+            animation: None,
+            view_angle: 0.0,
+        });
+    }
+    for decoration in &world.decorations {
+        sprites.push(Sprite {
+            name: &decoration.sprite,
+            x: decoration.x,
+            y: decoration.y,
+            size: DECORATION_SIZE,
+            lift: 0.0,
+            animation: None,
+            view_angle: 0.0,
+        });
+    }
+
+    let tile_size = world.map.tile_size;
+    let mut projected: Vec<(Sprite, SpriteRect)> = sprites
+        .into_iter()
+        .filter_map(|sprite| {
+            let rect = sprite_rect(&sprite, &world.player, tile_size, camera)?;
+            Some((sprite, rect))
+        })
+        .collect();
+    projected.sort_by(|(_, a), (_, b)| b.depth.total_cmp(&a.depth));
+    projected
+}
+
+//billboard box for a sprite, as wide as it is tall and centered where it projects
+//it is scaled and placed with the same depth as walls, so it stands on the floor under it
+//None if it is behind the camera
+pub fn sprite_rect(
+    sprite: &Sprite,
+    player: &Player,
+    tile_size: f64,
+    camera: &Camera,
+) -> Option<SpriteRect> {
+    let p = projection::project_point(player, camera, sprite.x, sprite.y)?;
+    let sprite_h = projection::wall_height(camera, tile_size, p.depth) * sprite.size;
+    let bottom = projection::screen_y(camera, tile_size, p.depth, sprite.lift * tile_size);
+    Some(SpriteRect {
+        x: p.x - sprite_h / 2.0,
+        y: bottom - sprite_h,
+        w: sprite_h,
+        h: sprite_h,
+        depth: p.depth,
+    })
+}
+
+//cuts a sprite into one slice per ray column it covers, keeping only columns where it is in
+//front of the wall, so a sprite half behind a wall is only half drawn
+pub fn sprite_slices(rect: &SpriteRect, depth: &[f64], camera: &Camera) -> Vec<SpriteSlice> {
+    let strip_w = camera.strip_width();
+    let left = rect.x.max(0.0);
+    let right = (rect.x + rect.w).min(camera.width);
+    let (first, last) = match (camera.column_at(left), camera.column_at(right - 1e-9)) {
+        (Some(first), Some(last)) if right > left => (first, last),
+        _ => return Vec::new(), //entirely off the view
+    };
+
+    (first..=last)
+        .filter(|&column| rect.depth < depth[column])
+        .map(|column| {
+            //the part of the column the sprite covers
+            let x0 = (column as f64 * strip_w).max(left);
+            let x1 = ((column + 1) as f64 * strip_w).min(right);
+            SpriteSlice {
+                x: x0,
+                w: x1 - x0,
+                u0: (x0 - rect.x) / rect.w,
+                u1: (x1 - rect.x) / rect.w,
+            }
+        })
+        .collect()
+}
+
+//color washed over the whole 3D view, red while the player is hurting and darker red once they
+//are dead, None when there is nothing to show
+pub fn damage_tint(world: &World) -> Option<[f32; 4]> {
+    if world.game_over() {
+        return Some(GAME_OVER_TINT);
+    }
+    if world.player.pain <= 0.0 {
+        return None;
+    }
+    let fade = (world.player.pain / PAIN_FLASH) as f32;
+    let [r, g, b, a] = PAIN_TINT;
+    Some([r, g, b, a * fade])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Level;
+
+    #[test]
+    fn sprites_are_ordered_by_the_depth_they_are_drawn_at() {
+        //player in tile (1, 2) facing east, a lamp straight ahead 100 away and a barrel off to the
+        //side that is further in a straight line (about 101) but only 90 deep
+        let text = "tile # 1\ntile . 0\nangle 0\n\
+                    decoration 3.0625 2.5 lamp\n\
+                    decoration 2.90625 3.203125 barrel\n\
+                    map\n########\n#......#\n#P.....#\n#......#\n########\n";
+        let world = World::from_level(Level::parse(text).unwrap());
+        let camera = Camera::new(60.0, 60, 600.0, 400.0);
+
+        let sprites = sorted_sprites(&world, &camera);
+        let names: Vec<&str> = sprites.iter().map(|(s, _)| s.name).collect();
+        assert_eq!(names, vec!["lamp", "barrel"]);
+        assert_eq!(sprites[0].1.depth, 100.0);
+        assert_eq!(sprites[1].1.depth, 90.0);
+    }
+
+    #[test]
+    fn sprites_behind_the_camera_are_left_out() {
+        let text = "tile # 1\ntile . 0\nangle 0\n\
+                    decoration 1.0 2.5 lamp\n\
+                    map\n#####\n#.P.#\n#####\n";
+        let world = World::from_level(Level::parse(text).unwrap());
+        let camera = Camera::new(60.0, 60, 600.0, 400.0);
+        assert!(sorted_sprites(&world, &camera).is_empty());
+    }
+}
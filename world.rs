@@ -0,0 +1,329 @@
+//WORLD STATE
+//everything that exists in the game, no drawing or input handling lives here
+
+use crate::ai::Brain;
+use crate::animation::Animator;
+use crate::damage::{Damage, DamageKind, Event, Health};
+use crate::level::Level;
+use crate::lighting::{Flash, Light, LightMap};
+use crate::map::Map;
+use crate::pathfinding::NavGrid;
+use crate::raycast::Face;
+use crate::rng::Rng;
+use std::f64::consts::PI;
+
+//hit points everything starts with
+pub const PLAYER_HP: f64 = 100.0;
+pub const ENEMY_HP: f64 = 60.0;
+
+//seconds the screen flashes red after the player is hurt
+pub const PAIN_FLASH: f64 = 0.4;
+
+//most projectiles flying at once, more shots are not fired until some land
+pub const MAX_PROJECTILES: usize = 64;
+
+//every level starts the random numbers from here so a run can be played back the same
+const RNG_SEED: u64 = 0x5eed;
+
+//HELPER FUNCTIONS
+//degrees to radians for later functions of cos, sin, tan
+pub fn deg_to_rad(a: f64) -> f64 {
+    a * PI / 180.0
+}
+
+//keeps player's angle between 0-359 degrees by wrapping around
+pub fn fix_angle(a: f64) -> f64 {
+    let mut angle = a;
+    if angle > 359.0 {
+        angle -= 360.0
+    }
+    if angle < 0.0 {
+        angle += 360.0
+    }
+    angle
+}
+
+//PLAYER STRUCTURE
+pub struct Player {
+    pub px: f64,        //player position x in (x, y)
+    pub py: f64,        //player position x in (x, y)
+    pub pdx: f64,       //x component of facing direction vecotr
+    pub pdy: f64,       //y component of facing direction vector
+    pub pa: f64,        //the facing angle of player
+    pub health: Health, //hit points and armor
+    pub pain: f64,      //seconds left of the red flash after being hurt
+}
+
+impl Player {
+    pub fn new(tile_x: usize, tile_y: usize, angle: f64, map: &Map) -> Self {
+        let pa: f64 = fix_angle(angle); //initial angle (90 is facing upward)
+        let pdx = deg_to_rad(pa).cos(); //x-component of facing direction
+        let pdy = -deg_to_rad(pa).sin(); //y-component of facing direction (negative because y increases down)
+        let (px, py) = map.tile_center(tile_x, tile_y); //initial position of player (center of start tile)
+        Self {
+            px,
+            py,
+            pdx,
+            pdy,
+            pa,
+            health: Health::new(PLAYER_HP),
+            pain: 0.0,
+        }
+    }
+}
+
+//ENEMY STRUCTURE
+pub struct Enemy {
+    pub x: f64,              //x position in world space
+    pub y: f64,              //y position in world space
+    pub facing: f64,         //angle the enemy faces in degrees, same as the player's (90 is up)
+    pub alive: bool,         //false once its health runs out
+    pub health: Health,      //hit points and armor
+    pub animation: Animator, //what the enemy's sprite is playing, death keeps playing after it dies
+    pub brain: Brain,        //AI state, see ai.rs
+}
+
+impl Enemy {
+    pub fn new(tile_x: usize, tile_y: usize, facing: f64, map: &Map) -> Self {
+        let (x, y) = map.tile_center(tile_x, tile_y); //center of tile
+        Self {
+            x,
+            y,
+            facing: fix_angle(facing),
+            alive: true,
+            health: Health::new(ENEMY_HP),
+            animation: Animator::new(),
+            brain: Brain::new(),
+        }
+    }
+
+    //angle from the enemy to a point, in the same degrees as facing
+    pub fn angle_to(&self, x: f64, y: f64) -> f64 {
+        let angle = (-(y - self.y)).atan2(x - self.x).to_degrees(); //y flipped because it increases down
+        fix_angle(angle)
+    }
+}
+
+//PROJECTILE STRUCTURE
+//who fired a projectile, it never hits its owner
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Owner {
+    Player,
+    Enemy(usize), //index in world.enemies
+}
+
+//anything fired that flies until it hits something, fizzles out or leaves the map
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Projectile {
+    pub x: f64,         //x world coordinate
+    pub y: f64,         //y world coordinate
+    pub dx: f64,        //x direction vector (unit length)
+    pub dy: f64,        //y direction vector (unit length)
+    pub speed: f64,     //pixels per second
+    pub damage: Damage, //what it does to whatever it hits
+    pub owner: Owner,
+    pub time_left: f64, //seconds before it fizzles out
+    pub active: bool,   //whether it is flying or its slot is free
+}
+
+//PROJECTILE POOL
+//a fixed number of slots reused as projectiles come and go, so firing never allocates
+pub struct ProjectilePool {
+    slots: Vec<Projectile>,
+}
+
+impl ProjectilePool {
+    pub fn new(capacity: usize) -> Self {
+        let empty = Projectile {
+            x: 0.0,
+            y: 0.0,
+            dx: 0.0,
+            dy: 0.0,
+            speed: 0.0,
+            damage: Damage {
+                amount: 0.0,
+                kind: DamageKind::Bullet,
+            },
+            owner: Owner::Player,
+            time_left: 0.0,
+            active: false,
+        };
+        Self {
+            slots: vec![empty; capacity],
+        }
+    }
+
+    //puts a projectile in the first free slot, false if every slot is flying already
+    pub fn spawn(&mut self, projectile: Projectile) -> bool {
+        match self.slots.iter_mut().find(|p| !p.active) {
+            Some(slot) => {
+                *slot = Projectile {
+                    active: true,
+                    ..projectile
+                };
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn active(&self) -> impl Iterator<Item = &Projectile> {
+        self.slots.iter().filter(|p| p.active)
+    }
+
+    pub fn active_mut(&mut self) -> impl Iterator<Item = &mut Projectile> {
+        self.slots.iter_mut().filter(|p| p.active)
+    }
+}
+
+//PICKUP STRUCTURE
+//something lying on the floor that the player collects by walking over it
+pub struct Pickup {
+    pub x: f64,         //x position in world space
+    pub y: f64,         //y position in world space
+    pub sprite: String, //sprite name in the texture manifest
+    pub taken: bool,    //picked up already, no longer drawn
+}
+
+//DECORATION STRUCTURE
+//scenery such as lamps and barrels, it is only there to be looked at
+pub struct Decoration {
+    pub x: f64,         //x position in world space
+    pub y: f64,         //y position in world space
+    pub sprite: String, //sprite name in the texture manifest
+}
+
+//WORLD STRUCTURE
+//the map plus everything moving around in it
+pub struct World {
+    pub map: Map,
+    pub nav: NavGrid, //where enemies can walk, starts out as the map's walls
+    pub player: Player,
+    pub enemies: Vec<Enemy>,
+    pub projectiles: ProjectilePool, //everything fired by the player and enemies
+    pub pickups: Vec<Pickup>,
+    pub decorations: Vec<Decoration>,
+    pub lights: Vec<Light>,   //lights placed in the level, they never change
+    pub flashes: Vec<Flash>,  //short lived lights such as muzzle flashes
+    pub base_light: LightMap, //ambient plus the level's lights, baked once
+    pub light_map: LightMap,  //base_light plus the flashes alive this frame, what gets drawn
+    pub events: Vec<Event>,   //what happened during the last update
+    pub rng: Rng,             //for gameplay rolls such as whether a shot hits
+}
+
+impl World {
+    pub fn from_level(level: Level) -> Self {
+        let (start_x, start_y) = level.player_start;
+        let player = Player::new(start_x, start_y, level.player_angle, &level.map);
+        //enemies start out looking at where the player comes in, those with a route walk it and
+        //some shoot hitscan instead of projectiles
+        let enemies = level
+            .enemies
+            .iter()
+            .map(|&(x, y)| {
+                let mut enemy = Enemy::new(x, y, 0.0, &level.map);
+                enemy.facing = enemy.angle_to(player.px, player.py);
+                if let Some((_, route)) = level.patrols.iter().find(|(start, _)| *start == (x, y)) {
+                    enemy.brain.waypoints = route
+                        .iter()
+                        .map(|&(wx, wy)| level.map.tile_center(wx, wy))
+                        .collect();
+                }
+                if let Some(&(_, kind)) = level.ranged.iter().find(|(s, _)| *s == (x, y)) {
+                    enemy.brain.ranged = kind;
+                }
+                enemy
+            })
+            .collect();
+
+        let ambient = [level.ambient; 3];
+        let base_light = LightMap::bake(&level.map, ambient, &level.lights);
+
+        Self {
+            nav: NavGrid::from_map(&level.map),
+            map: level.map,
+            player,
+            enemies,
+            projectiles: ProjectilePool::new(MAX_PROJECTILES),
+            pickups: level.pickups,
+            decorations: level.decorations,
+            lights: level.lights,
+            flashes: Vec::new(),
+            light_map: base_light.clone(),
+            base_light,
+            events: Vec::new(),
+            rng: Rng::new(RNG_SEED),
+        }
+    }
+
+    //the player is dead, nothing they do counts until the level is restarted
+    pub fn game_over(&self) -> bool {
+        self.player.health.is_dead()
+    }
+
+    //light on the floor at a world position, sprites standing there are lit the same
+    pub fn light_at(&self, x: f64, y: f64) -> [f32; 3] {
+        let (tx, ty) = self.map.to_tile(x, y);
+        self.light_map.tile(tx, ty)
+    }
+
+    //light on the wall face a strip shows, before side shading
+    pub fn wall_light(&self, cell: (i32, i32), face: Face) -> [f32; 3] {
+        self.light_map.face(cell.0, cell.1, face)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shot(x: f64) -> Projectile {
+        Projectile {
+            x,
+            y: 0.0,
+            dx: 1.0,
+            dy: 0.0,
+            speed: 100.0,
+            damage: Damage {
+                amount: 10.0,
+                kind: DamageKind::Bullet,
+            },
+            owner: Owner::Player,
+            time_left: 1.0,
+            active: true,
+        }
+    }
+
+    #[test]
+    fn spawn_fails_once_every_slot_is_flying() {
+        let mut pool = ProjectilePool::new(MAX_PROJECTILES);
+        assert_eq!(pool.active().count(), 0);
+        for i in 0..MAX_PROJECTILES {
+            assert!(pool.spawn(shot(i as f64)));
+        }
+        assert!(!pool.spawn(shot(-1.0)));
+        assert_eq!(pool.active().count(), MAX_PROJECTILES);
+        assert!(
+            pool.active().all(|p| p.x >= 0.0),
+            "the extra shot must not replace one"
+        );
+    }
+
+    #[test]
+    fn landed_projectiles_free_their_slot() {
+        let mut pool = ProjectilePool::new(3);
+        for i in 0..3 {
+            pool.spawn(shot(i as f64));
+        }
+        //the middle one lands
+        pool.active_mut().nth(1).unwrap().active = false;
+        assert_eq!(pool.active().count(), 2);
+
+        let mut inactive = shot(9.0);
+        inactive.active = false; //spawning always makes it fly
+        assert!(pool.spawn(inactive));
+        let xs: Vec<f64> = pool.active().map(|p| p.x).collect();
+        assert_eq!(xs, vec![0.0, 9.0, 2.0]);
+        assert!(!pool.spawn(shot(10.0)));
+    }
+}